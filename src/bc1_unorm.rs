@@ -0,0 +1,162 @@
+use crate::{
+    error::DecodeError,
+    pixel::{replicate_bits, RGB, RGBA},
+};
+
+pub const BLOCK_SIZE: usize = 8;
+
+/// Expand a packed 5:6:5 colour to 8 bits per channel by replicating the top bits
+fn unpack_565(value: u16) -> RGB {
+    let value = value as u32;
+    RGB {
+        r: replicate_bits(value >> 11, 5, 8) as u8,
+        g: replicate_bits((value >> 5) & 0x3f, 6, 8) as u8,
+        b: replicate_bits(value & 0x1f, 5, 8) as u8,
+    }
+}
+
+/// Weighted average of two colours, weights out of the given total
+fn blend(c0: RGB, c1: RGB, w0: u16, w1: u16) -> RGB {
+    let mix = |a: u8, b: u8| ((a as u16 * w0 + b as u16 * w1) / (w0 + w1)) as u8;
+    RGB {
+        r: mix(c0.r, c1.r),
+        g: mix(c0.g, c1.g),
+        b: mix(c0.b, c1.b),
+    }
+}
+
+/// Decode the 8 byte colour half shared by BC1, BC2 and BC3.
+/// With `punch_through`, endpoints with c0 <= c1 select the 3-colour mode where index 3
+/// is transparent black. BC2 and BC3 always use the 4-colour mode
+pub(crate) fn decode_colors(block: &[u8], punch_through: bool) -> [RGBA; 16] {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+
+    // Ordering is decided on the packed values, not the expanded ones
+    let (e0, e1) = (unpack_565(c0), unpack_565(c1));
+    let palette: [RGBA; 4] = if c0 > c1 || !punch_through {
+        [
+            e0.into(),
+            e1.into(),
+            blend(e0, e1, 2, 1).into(),
+            blend(e0, e1, 1, 2).into(),
+        ]
+    } else {
+        [
+            e0.into(),
+            e1.into(),
+            blend(e0, e1, 1, 1).into(),
+            RGBA {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            },
+        ]
+    };
+
+    // 2 bits per pixel, first pixel in the lowest bits
+    let mut pixels = [palette[0]; 16];
+    pixels.iter_mut().enumerate().for_each(|(i, p)| {
+        *p = palette[(indices >> (i * 2)) as usize & 0b11];
+    });
+
+    pixels
+}
+
+/// Decode a single 8 byte BC1 block into a row-major 4x4 grid of pixels
+pub fn decode_bc1_block(block: &[u8]) -> Result<[RGBA; 16], DecodeError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(DecodeError::InvalidBlockLength {
+            expected: BLOCK_SIZE,
+            actual: block.len(),
+        });
+    }
+
+    Ok(decode_colors(block, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_bc1_block, unpack_565};
+    use crate::{
+        error::DecodeError,
+        pixel::{RGB, RGBA},
+    };
+
+    fn build_block(c0: u16, c1: u16, indices: u32) -> [u8; 8] {
+        let mut block = [0; 8];
+        block[..2].copy_from_slice(&c0.to_le_bytes());
+        block[2..4].copy_from_slice(&c1.to_le_bytes());
+        block[4..].copy_from_slice(&indices.to_le_bytes());
+        block
+    }
+
+    fn rgba(r: u8, g: u8, b: u8, a: u8) -> RGBA {
+        RGBA { r, g, b, a }
+    }
+
+    #[test]
+    fn test_unpack_565() {
+        assert_eq!(
+            unpack_565(0xffff),
+            RGB {
+                r: 255,
+                g: 255,
+                b: 255
+            }
+        );
+        assert_eq!(unpack_565(0xf800), RGB { r: 255, g: 0, b: 0 });
+        assert_eq!(unpack_565(0x0400), RGB { r: 0, g: 130, b: 0 });
+    }
+
+    #[test]
+    fn test_four_colour_mode() {
+        // White to black, pixel i uses index i % 4
+        let block = build_block(0xffff, 0x0000, 0xe4e4e4e4);
+        let pixels = decode_bc1_block(&block).unwrap();
+        assert_eq!(
+            pixels[..4],
+            [
+                rgba(255, 255, 255, 255),
+                rgba(0, 0, 0, 255),
+                rgba(170, 170, 170, 255),
+                rgba(85, 85, 85, 255),
+            ]
+        );
+        assert_eq!(pixels[..4], pixels[12..]);
+    }
+
+    #[test]
+    fn test_three_colour_mode() {
+        // Black to white, so c0 <= c1
+        let block = build_block(0x0000, 0xffff, 0xe4e4e4e4);
+        let pixels = decode_bc1_block(&block).unwrap();
+        assert_eq!(
+            pixels[..4],
+            [
+                rgba(0, 0, 0, 255),
+                rgba(255, 255, 255, 255),
+                rgba(127, 127, 127, 255),
+                rgba(0, 0, 0, 0),
+            ]
+        );
+
+        // Equal endpoints also use the 3-colour mode
+        let block = build_block(0xf800, 0xf800, 0xffffffff);
+        let pixels = decode_bc1_block(&block).unwrap();
+        assert!(pixels.iter().all(|p| p.a == 0));
+    }
+
+    #[test]
+    fn test_invalid_block_length() {
+        assert!(matches!(
+            decode_bc1_block(&[0; 16]),
+            Err(DecodeError::InvalidBlockLength {
+                expected: 8,
+                actual: 16
+            })
+        ));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::{bc1_unorm::decode_colors, error::DecodeError, pixel::RGBA};
+
+pub const BLOCK_SIZE: usize = 16;
+
+/// Decode a single 16 byte BC2 block into a row-major 4x4 grid of pixels.
+/// The first 8 bytes are explicit 4 bit alpha values, the rest a BC1 colour block
+pub fn decode_bc2_block(block: &[u8]) -> Result<[RGBA; 16], DecodeError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(DecodeError::InvalidBlockLength {
+            expected: BLOCK_SIZE,
+            actual: block.len(),
+        });
+    }
+
+    let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+    let mut pixels = decode_colors(&block[8..], false);
+    pixels.iter_mut().enumerate().for_each(|(i, p)| {
+        // 4 bits per pixel, first pixel in the lowest bits. * 17 replicates the nibble
+        p.a = ((alpha >> (i * 4)) as u8 & 0xf) * 17;
+    });
+
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::decode_bc2_block;
+
+    #[test]
+    fn test_explicit_alpha() {
+        // Alpha counts up 0..16, colour is solid red with c0 == c1, which BC2 still
+        // decodes in 4-colour mode
+        let alpha = (0..16u64).fold(0, |acc, i| acc | (i << (i * 4)));
+        let mut block = [0; 16];
+        block[..8].copy_from_slice(&alpha.to_le_bytes());
+        block[8..12].copy_from_slice(&[0x00, 0xf8, 0x00, 0xf8]);
+        block[12..].copy_from_slice(&[0xff; 4]);
+
+        let pixels = decode_bc2_block(&block).unwrap();
+        pixels.iter().enumerate().for_each(|(i, p)| {
+            assert_eq!([p.r, p.g, p.b, p.a], [255, 0, 0, i as u8 * 17]);
+        });
+    }
+}
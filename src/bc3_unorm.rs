@@ -0,0 +1,87 @@
+use crate::{bc1_unorm::decode_colors, error::DecodeError, pixel::RGBA};
+
+pub const BLOCK_SIZE: usize = 16;
+
+/// Build the 8 entry palette for an interpolated alpha block, also used by BC4 and BC5.
+/// a0 > a1 gives 6 interpolated values, otherwise 4 plus explicit 0 and 255
+pub(crate) fn alpha_palette(a0: u8, a1: u8) -> [u8; 8] {
+    let (a0, a1) = (a0 as u16, a1 as u16);
+    let mut palette = [0; 8];
+    palette[0] = a0 as u8;
+    palette[1] = a1 as u8;
+
+    if a0 > a1 {
+        (1..7).for_each(|i| palette[i + 1] = (((7 - i as u16) * a0 + i as u16 * a1) / 7) as u8);
+    } else {
+        (1..5).for_each(|i| palette[i + 1] = (((5 - i as u16) * a0 + i as u16 * a1) / 5) as u8);
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    palette
+}
+
+/// Split the 48 bits following the two endpoints into 3 bit indices, first pixel lowest
+pub(crate) fn alpha_indices(block: &[u8]) -> [usize; 16] {
+    let mut bits = [0; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let bits = u64::from_le_bytes(bits);
+
+    let mut indices = [0; 16];
+    indices
+        .iter_mut()
+        .enumerate()
+        .for_each(|(i, index)| *index = (bits >> (i * 3)) as usize & 0b111);
+
+    indices
+}
+
+/// Decode a single 16 byte BC3 block into a row-major 4x4 grid of pixels.
+/// The first 8 bytes are an interpolated alpha block, the rest a BC1 colour block
+pub fn decode_bc3_block(block: &[u8]) -> Result<[RGBA; 16], DecodeError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(DecodeError::InvalidBlockLength {
+            expected: BLOCK_SIZE,
+            actual: block.len(),
+        });
+    }
+
+    let palette = alpha_palette(block[0], block[1]);
+    let indices = alpha_indices(block);
+    let mut pixels = decode_colors(&block[8..], false);
+    pixels
+        .iter_mut()
+        .zip(indices)
+        .for_each(|(p, index)| p.a = palette[index]);
+
+    Ok(pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{alpha_palette, decode_bc3_block};
+
+    #[test]
+    fn test_alpha_palette() {
+        assert_eq!(alpha_palette(255, 0), [255, 0, 218, 182, 145, 109, 72, 36]);
+        assert_eq!(alpha_palette(0, 255), [0, 255, 51, 102, 153, 204, 0, 255]);
+    }
+
+    #[test]
+    fn test_interpolated_alpha() {
+        // Pixel i uses alpha index i % 8
+        let indices = (0..16u64).fold(0, |acc, i| acc | ((i % 8) << (i * 3)));
+        let mut block = [0; 16];
+        block[0] = 0x10;
+        block[1] = 0xf0;
+        block[2..8].copy_from_slice(&indices.to_le_bytes()[..6]);
+        // Solid white colour block
+        block[8..12].copy_from_slice(&[0xff; 4]);
+
+        let pixels = decode_bc3_block(&block).unwrap();
+        let alpha = pixels.iter().map(|p| p.a).collect::<Vec<_>>();
+        assert_eq!(alpha[..8], alpha_palette(0x10, 0xf0));
+        assert_eq!(alpha[..8], alpha[8..]);
+        assert!(pixels.iter().all(|p| [p.r, p.g, p.b] == [255, 255, 255]));
+    }
+}
@@ -0,0 +1,105 @@
+use crate::{
+    bc3_unorm::{alpha_indices, alpha_palette},
+    error::DecodeError,
+};
+
+pub const BLOCK_SIZE: usize = 8;
+
+/// Build the 8 entry palette for a signed block. Both -127 and -128 mean -1.0,
+/// so endpoints are clamped to -127 before interpolating
+fn snorm_palette(r0: i8, r1: i8) -> [i8; 8] {
+    let (r0, r1) = (r0.max(-127) as i16, r1.max(-127) as i16);
+    let mut palette = [0; 8];
+    palette[0] = r0 as i8;
+    palette[1] = r1 as i8;
+
+    if r0 > r1 {
+        (1..7).for_each(|i| palette[i + 1] = (((7 - i as i16) * r0 + i as i16 * r1) / 7) as i8);
+    } else {
+        (1..5).for_each(|i| palette[i + 1] = (((5 - i as i16) * r0 + i as i16 * r1) / 5) as i8);
+        palette[6] = -127;
+        palette[7] = 127;
+    }
+
+    palette
+}
+
+/// Decode a single 8 byte BC4_UNORM block into a row-major 4x4 grid of values
+pub fn decode_bc4_unorm_block(block: &[u8]) -> Result<[u8; 16], DecodeError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(DecodeError::InvalidBlockLength {
+            expected: BLOCK_SIZE,
+            actual: block.len(),
+        });
+    }
+
+    let palette = alpha_palette(block[0], block[1]);
+    Ok(alpha_indices(block).map(|i| palette[i]))
+}
+
+/// Decode a single 8 byte BC4_SNORM block into a row-major 4x4 grid of values
+pub fn decode_bc4_snorm_block(block: &[u8]) -> Result<[i8; 16], DecodeError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(DecodeError::InvalidBlockLength {
+            expected: BLOCK_SIZE,
+            actual: block.len(),
+        });
+    }
+
+    let palette = snorm_palette(block[0] as i8, block[1] as i8);
+    Ok(alpha_indices(block).map(|i| palette[i]))
+}
+
+/// Remap a signed value from [-127, 127] onto [0, 255] for display
+pub fn snorm_to_unorm(value: i8) -> u8 {
+    ((value.max(-127) as i32 + 127) * 255 / 254) as u8
+}
+
+/// Convert a signed value from [-127, 127] to [-1.0, 1.0]
+pub fn snorm_to_f32(value: i8) -> f32 {
+    value.max(-127) as f32 / 127.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_bc4_snorm_block, decode_bc4_unorm_block, snorm_palette, snorm_to_unorm};
+
+    #[test]
+    fn test_unorm() {
+        // 6 value mode, pixel i uses index i % 8
+        let indices = (0..16u64).fold(0, |acc, i| acc | ((i % 8) << (i * 3)));
+        let mut block = [0; 8];
+        block[0] = 0;
+        block[1] = 100;
+        block[2..].copy_from_slice(&indices.to_le_bytes()[..6]);
+
+        let values = decode_bc4_unorm_block(&block).unwrap();
+        assert_eq!(values[..8], [0, 100, 20, 40, 60, 80, 0, 255]);
+    }
+
+    #[test]
+    fn test_snorm_palette() {
+        assert_eq!(
+            snorm_palette(127, -127),
+            [127, -127, 90, 54, 18, -18, -54, -90]
+        );
+        // -128 is treated as -127
+        assert_eq!(snorm_palette(-128, 127), snorm_palette(-127, 127));
+        assert_eq!(
+            snorm_palette(-127, 127),
+            [-127, 127, -76, -25, 25, 76, -127, 127]
+        );
+    }
+
+    #[test]
+    fn test_snorm() {
+        // Every pixel uses index 0, the first endpoint
+        let block = [0x80, 0x00, 0, 0, 0, 0, 0, 0];
+        let values = decode_bc4_snorm_block(&block).unwrap();
+        assert!(values.iter().all(|&v| v == -127));
+
+        assert_eq!(snorm_to_unorm(-128), 0);
+        assert_eq!(snorm_to_unorm(0), 127);
+        assert_eq!(snorm_to_unorm(127), 255);
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{
+    bc4::{self, decode_bc4_snorm_block, decode_bc4_unorm_block},
+    error::DecodeError,
+};
+
+pub const BLOCK_SIZE: usize = 16;
+
+/// Decode a single 16 byte BC5_UNORM block into a row-major 4x4 grid of (red, green) values.
+/// Each channel is stored as its own BC4 block, red first
+pub fn decode_bc5_unorm_block(block: &[u8]) -> Result<[[u8; 2]; 16], DecodeError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(DecodeError::InvalidBlockLength {
+            expected: BLOCK_SIZE,
+            actual: block.len(),
+        });
+    }
+
+    let red = decode_bc4_unorm_block(&block[..bc4::BLOCK_SIZE])?;
+    let green = decode_bc4_unorm_block(&block[bc4::BLOCK_SIZE..])?;
+
+    let mut pixels = [[0; 2]; 16];
+    pixels
+        .iter_mut()
+        .enumerate()
+        .for_each(|(i, p)| *p = [red[i], green[i]]);
+    Ok(pixels)
+}
+
+/// Decode a single 16 byte BC5_SNORM block into a row-major 4x4 grid of (red, green) values
+pub fn decode_bc5_snorm_block(block: &[u8]) -> Result<[[i8; 2]; 16], DecodeError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(DecodeError::InvalidBlockLength {
+            expected: BLOCK_SIZE,
+            actual: block.len(),
+        });
+    }
+
+    let red = decode_bc4_snorm_block(&block[..bc4::BLOCK_SIZE])?;
+    let green = decode_bc4_snorm_block(&block[bc4::BLOCK_SIZE..])?;
+
+    let mut pixels = [[0; 2]; 16];
+    pixels
+        .iter_mut()
+        .enumerate()
+        .for_each(|(i, p)| *p = [red[i], green[i]]);
+    Ok(pixels)
+}
+
+/// Z of a unit length tangent-space normal, given X and Y in [-1.0, 1.0]
+pub fn reconstruct_z(x: f32, y: f32) -> f32 {
+    (1.0 - x * x - y * y).max(0.0).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_bc5_snorm_block, decode_bc5_unorm_block, reconstruct_z};
+
+    #[test]
+    fn test_channels() {
+        // Solid 10 red, solid 200 green
+        let block = [10, 10, 0, 0, 0, 0, 0, 0, 200, 200, 0, 0, 0, 0, 0, 0];
+        let pixels = decode_bc5_unorm_block(&block).unwrap();
+        assert!(pixels.iter().all(|&p| p == [10, 200]));
+
+        let pixels = decode_bc5_snorm_block(&block).unwrap();
+        assert!(pixels.iter().all(|&p| p == [10, -56]));
+    }
+
+    #[test]
+    fn test_reconstruct_z() {
+        assert_eq!(reconstruct_z(0.0, 0.0), 1.0);
+        assert_eq!(reconstruct_z(1.0, 0.0), 0.0);
+        assert!((reconstruct_z(0.6, 0.0) - 0.8).abs() < 1e-6);
+        // Slightly out of range inputs don't produce NaN
+        assert_eq!(reconstruct_z(1.0, 0.5), 0.0);
+    }
+}
@@ -0,0 +1,404 @@
+use bitvec::{field::BitField, order::Lsb0, slice::BitSlice, view::BitView};
+use half::f16;
+
+use crate::{
+    bptc::{read_indices, FIXUP_TABLE_2, PARTITION_TABLE_2, WEIGHTS_3, WEIGHTS_4},
+    error::DecodeError,
+};
+
+/// Size of a single compressed 4x4 block in bytes
+pub const BLOCK_SIZE: usize = 16;
+
+/// Destination of a run of header bits
+#[derive(Debug, Copy, Clone)]
+enum Field {
+    /// Endpoint w, x, y or z, then channel r, g or b
+    Endpoint(usize, usize),
+    Partition,
+}
+
+// Names as used in the format reference. w and x are the endpoints of region 0, y and z of region 1
+const RW: Field = Field::Endpoint(0, 0);
+const RX: Field = Field::Endpoint(1, 0);
+const RY: Field = Field::Endpoint(2, 0);
+const RZ: Field = Field::Endpoint(3, 0);
+const GW: Field = Field::Endpoint(0, 1);
+const GX: Field = Field::Endpoint(1, 1);
+const GY: Field = Field::Endpoint(2, 1);
+const GZ: Field = Field::Endpoint(3, 1);
+const BW: Field = Field::Endpoint(0, 2);
+const BX: Field = Field::Endpoint(1, 2);
+const BY: Field = Field::Endpoint(2, 2);
+const BZ: Field = Field::Endpoint(3, 2);
+const D: Field = Field::Partition;
+
+/// Bit layout of a single BC6H mode, as given in the mode reference table
+#[derive(Debug)]
+struct ModeInfo {
+    /// Value of the mode field, 2 bits wide for the first two modes and 5 bits for the rest
+    id: u8,
+    regions: usize,
+    /// Whether x, y and z are stored as deltas from w
+    transformed: bool,
+    /// Precision of w, and of the other endpoints when they aren't deltas
+    endpoint_bits: u32,
+    /// Precision of the stored r, g and b deltas
+    delta_bits: [u32; 3],
+    /// Header fields in stream order. `(field, a, b)` is `field[a:b]` in the reference:
+    /// bits b through a, with b read first. Some modes store bits in reverse, e.g. `rw[10:15]`
+    fields: &'static [(Field, u32, u32)],
+}
+
+#[rustfmt::skip]
+const MODES: [ModeInfo; 14] = [
+    ModeInfo { id: 0b00, regions: 2, transformed: true, endpoint_bits: 10, delta_bits: [5, 5, 5], fields: &[
+        (GY, 4, 4), (BY, 4, 4), (BZ, 4, 4), (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 4, 0), (GZ, 4, 4),
+        (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0),
+        (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+    ] },
+    ModeInfo { id: 0b01, regions: 2, transformed: true, endpoint_bits: 7, delta_bits: [6, 6, 6], fields: &[
+        (GY, 5, 5), (GZ, 4, 4), (GZ, 5, 5), (RW, 6, 0), (BZ, 0, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 6, 0),
+        (BY, 5, 5), (BZ, 2, 2), (GY, 4, 4), (BW, 6, 0), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 5, 0),
+        (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 5, 0), (BY, 3, 0), (RY, 5, 0), (RZ, 5, 0), (D, 4, 0),
+    ] },
+    ModeInfo { id: 0b00010, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [5, 4, 4], fields: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 4, 0), (RW, 10, 10), (GY, 3, 0), (GX, 3, 0), (GW, 10, 10),
+        (BZ, 0, 0), (GZ, 3, 0), (BX, 3, 0), (BW, 10, 10), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2),
+        (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+    ] },
+    ModeInfo { id: 0b00110, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 5, 4], fields: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 10), (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0),
+        (GW, 10, 10), (GZ, 3, 0), (BX, 3, 0), (BW, 10, 10), (BZ, 1, 1), (BY, 3, 0), (RY, 3, 0), (BZ, 0, 0),
+        (BZ, 2, 2), (RZ, 3, 0), (GY, 4, 4), (BZ, 3, 3), (D, 4, 0),
+    ] },
+    ModeInfo { id: 0b01010, regions: 2, transformed: true, endpoint_bits: 11, delta_bits: [4, 4, 5], fields: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 10), (BY, 4, 4), (GY, 3, 0), (GX, 3, 0),
+        (GW, 10, 10), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BW, 10, 10), (BY, 3, 0), (RY, 3, 0), (BZ, 1, 1),
+        (BZ, 2, 2), (RZ, 3, 0), (BZ, 4, 4), (BZ, 3, 3), (D, 4, 0),
+    ] },
+    ModeInfo { id: 0b01110, regions: 2, transformed: true, endpoint_bits: 9, delta_bits: [5, 5, 5], fields: &[
+        (RW, 8, 0), (BY, 4, 4), (GW, 8, 0), (GY, 4, 4), (BW, 8, 0), (BZ, 4, 4), (RX, 4, 0), (GZ, 4, 4),
+        (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0),
+        (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+    ] },
+    ModeInfo { id: 0b10010, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [6, 5, 5], fields: &[
+        (RW, 7, 0), (GZ, 4, 4), (BY, 4, 4), (GW, 7, 0), (BZ, 2, 2), (GY, 4, 4), (BW, 7, 0), (BZ, 3, 3),
+        (BZ, 4, 4), (RX, 5, 0), (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1),
+        (BY, 3, 0), (RY, 5, 0), (RZ, 5, 0), (D, 4, 0),
+    ] },
+    ModeInfo { id: 0b10110, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 6, 5], fields: &[
+        (RW, 7, 0), (BZ, 0, 0), (BY, 4, 4), (GW, 7, 0), (GY, 5, 5), (GY, 4, 4), (BW, 7, 0), (GZ, 5, 5),
+        (BZ, 4, 4), (RX, 4, 0), (GZ, 4, 4), (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1),
+        (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+    ] },
+    ModeInfo { id: 0b11010, regions: 2, transformed: true, endpoint_bits: 8, delta_bits: [5, 5, 6], fields: &[
+        (RW, 7, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 7, 0), (BY, 5, 5), (GY, 4, 4), (BW, 7, 0), (BZ, 5, 5),
+        (BZ, 4, 4), (RX, 4, 0), (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 5, 0),
+        (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+    ] },
+    ModeInfo { id: 0b11110, regions: 2, transformed: false, endpoint_bits: 6, delta_bits: [6, 6, 6], fields: &[
+        (RW, 5, 0), (GZ, 4, 4), (BZ, 0, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 5, 0), (GY, 5, 5), (BY, 5, 5),
+        (BZ, 2, 2), (GY, 4, 4), (BW, 5, 0), (GZ, 5, 5), (BZ, 3, 3), (BZ, 5, 5), (BZ, 4, 4), (RX, 5, 0),
+        (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 5, 0), (BY, 3, 0), (RY, 5, 0), (RZ, 5, 0), (D, 4, 0),
+    ] },
+    ModeInfo { id: 0b00011, regions: 1, transformed: false, endpoint_bits: 10, delta_bits: [10, 10, 10], fields: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 9, 0), (GX, 9, 0), (BX, 9, 0),
+    ] },
+    ModeInfo { id: 0b00111, regions: 1, transformed: true, endpoint_bits: 11, delta_bits: [9, 9, 9], fields: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 8, 0), (RW, 10, 10), (GX, 8, 0), (GW, 10, 10), (BX, 8, 0),
+        (BW, 10, 10),
+    ] },
+    ModeInfo { id: 0b01011, regions: 1, transformed: true, endpoint_bits: 12, delta_bits: [8, 8, 8], fields: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 7, 0), (RW, 10, 11), (GX, 7, 0), (GW, 10, 11), (BX, 7, 0),
+        (BW, 10, 11),
+    ] },
+    ModeInfo { id: 0b01111, regions: 1, transformed: true, endpoint_bits: 16, delta_bits: [4, 4, 4], fields: &[
+        (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 15), (GX, 3, 0), (GW, 10, 15), (BX, 3, 0),
+        (BW, 10, 15),
+    ] },
+];
+
+/// Decode a single 16 byte BC6H block into a row-major 4x4 grid of linear RGB values.
+/// `signed` selects SF16 rather than UF16
+pub fn decode_bc6h_block(block: &[u8], signed: bool) -> Result<[[f32; 3]; 16], DecodeError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(DecodeError::InvalidBlockLength {
+            expected: BLOCK_SIZE,
+            actual: block.len(),
+        });
+    }
+    let block = block.view_bits::<Lsb0>();
+
+    // The first two modes only use 2 mode bits, the rest 5
+    let id = match block[..2].load::<u8>() {
+        short @ (0b00 | 0b01) => short,
+        _ => block[..5].load::<u8>(),
+    };
+    let Some(info) = MODES.iter().find(|m| m.id == id) else {
+        // Reserved modes decode to black
+        trace!("reserved mode {id:#07b}");
+        return Ok([[0.0; 3]; 16]);
+    };
+
+    Ok(decode_block(block, info, signed))
+}
+
+fn decode_block(block: &BitSlice<u8, Lsb0>, info: &ModeInfo, signed: bool) -> [[f32; 3]; 16] {
+    // =========================== Step 1: Scatter the header bits into their fields
+    let mut offset = if info.id < 0b10 { 2 } else { 5 };
+    let mut endpoints = [[0i32; 3]; 4];
+    let mut partition = 0;
+    info.fields.iter().for_each(|&(field, a, b)| {
+        let bits = if a >= b {
+            (b..=a).collect::<Vec<_>>()
+        } else {
+            (a..=b).rev().collect()
+        };
+        bits.into_iter().for_each(|bit| {
+            let value = (block[offset] as i32) << bit;
+            match field {
+                Field::Endpoint(e, c) => endpoints[e][c] |= value,
+                Field::Partition => partition |= value as usize,
+            }
+            offset += 1;
+        });
+    });
+    let num_endpoints = info.regions * 2;
+    trace!(
+        "mode {:#07b}: partition {partition}, raw endpoints {endpoints:?}",
+        info.id
+    );
+
+    // ======================== Step 2: Undo the delta transform and unquantize
+    let endpoint_bits = info.endpoint_bits;
+    if signed {
+        endpoints[0] = endpoints[0].map(|v| sign_extend(v, endpoint_bits));
+    }
+    (1..num_endpoints).for_each(|e| {
+        (0..3).for_each(|c| {
+            let mut value = endpoints[e][c];
+            if info.transformed {
+                let delta = sign_extend(value, info.delta_bits[c]);
+                value = (endpoints[0][c] + delta) & ((1 << endpoint_bits) - 1);
+            }
+            if signed {
+                value = sign_extend(value, endpoint_bits);
+            }
+            endpoints[e][c] = value;
+        })
+    });
+    let endpoints = endpoints.map(|e| e.map(|v| unquantize(v, endpoint_bits, signed)));
+    trace!("endpoints {endpoints:?}");
+
+    // Table selection
+    let (partition_table, anchors, index_bits): ([usize; 16], &[usize], usize) = match info.regions
+    {
+        1 => ([0; 16], &[0], 4),
+        _ => (PARTITION_TABLE_2[partition], &FIXUP_TABLE_2[partition], 3),
+    };
+    let indices = read_indices(&block[offset..], anchors, index_bits);
+
+    // ========================= Step 3: Lerp em!
+    let weights: &[u16] = if index_bits == 3 {
+        &WEIGHTS_3
+    } else {
+        &WEIGHTS_4
+    };
+    let mut pixels = [[0.0; 3]; 16];
+    pixels.iter_mut().enumerate().for_each(|(i, pixel)| {
+        let region = partition_table[i];
+        let (e0, e1) = (endpoints[region * 2], endpoints[region * 2 + 1]);
+        let weight = weights[indices[i] as usize] as i32;
+
+        *pixel = [0, 1, 2].map(|c| {
+            let value = ((64 - weight) * e0[c] + weight * e1[c] + 32) >> 6;
+            finish_unquantize(value, signed).to_f32()
+        });
+    });
+
+    pixels
+}
+
+fn sign_extend(value: i32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    (value << shift) >> shift
+}
+
+/// Scale an endpoint up to the full 16 bit interpolation range
+fn unquantize(value: i32, bits: u32, signed: bool) -> i32 {
+    if !signed {
+        if bits >= 15 || value == 0 {
+            value
+        } else if value == (1 << bits) - 1 {
+            0xffff
+        } else {
+            ((value << 16) + 0x8000) >> bits
+        }
+    } else {
+        let magnitude = value.abs();
+        let unquantized = if bits >= 16 || magnitude == 0 {
+            magnitude
+        } else if magnitude >= (1 << (bits - 1)) - 1 {
+            0x7fff
+        } else {
+            ((magnitude << 15) + 0x4000) >> (bits - 1)
+        };
+
+        if value < 0 {
+            -unquantized
+        } else {
+            unquantized
+        }
+    }
+}
+
+/// Scale an interpolated value down into a half float, by 31/64 for unsigned and 31/32 for
+/// signed, so the result never reaches the infinity and NaN encodings
+fn finish_unquantize(value: i32, signed: bool) -> f16 {
+    if !signed {
+        f16::from_bits(((value * 31) >> 6) as u16)
+    } else if value < 0 {
+        f16::from_bits(0x8000 | (((-value) * 31) >> 5) as u16)
+    } else {
+        f16::from_bits(((value * 31) >> 5) as u16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode_bc6h_block, finish_unquantize, unquantize, MODES};
+    use crate::{error::DecodeError, test_util::build_block};
+
+    #[test]
+    fn test_mode_layouts() {
+        // Every mode has an 82 bit header for 2 regions or a 65 bit header for 1
+        MODES.iter().for_each(|m| {
+            let mode_bits = if m.id < 0b10 { 2 } else { 5 };
+            let field_bits = m
+                .fields
+                .iter()
+                .map(|&(_, a, b)| a.abs_diff(b) + 1)
+                .sum::<u32>();
+            let expected = if m.regions == 2 { 82 } else { 65 };
+            assert_eq!(mode_bits + field_bits, expected, "mode {:#07b}", m.id);
+        });
+    }
+
+    #[test]
+    fn test_unquantize() {
+        assert_eq!(unquantize(0, 10, false), 0);
+        assert_eq!(unquantize(1023, 10, false), 0xffff);
+        assert_eq!(unquantize(512, 10, false), 32800);
+        assert_eq!(unquantize(0x8000, 16, false), 0x8000);
+        assert_eq!(unquantize(-1, 10, true), -96);
+        assert_eq!(unquantize(511, 10, true), 0x7fff);
+
+        // The largest unsigned value is the largest finite half
+        assert_eq!(finish_unquantize(0xffff, false).to_f32(), 65504.0);
+        assert_eq!(finish_unquantize(-0x7fff, true).to_f32(), -65504.0);
+    }
+
+    #[test]
+    fn test_mode11_solid() {
+        // 10 bit endpoints stored as is, all indices 0
+        let fields = [
+            (0b00011, 5),
+            (512, 10),
+            (0, 10),
+            (1023, 10),
+            (0, 30),
+            (0, 63),
+        ];
+        let pixels = decode_bc6h_block(&build_block(&fields), false).unwrap();
+        assert!(pixels.iter().all(|&p| p == [1.5146484, 0.0, 65504.0]));
+    }
+
+    #[test]
+    fn test_mode1_deltas() {
+        // w = 512 everywhere, x has no delta, y has a red delta of +1 and z of -1
+        let fields = [
+            (0b00, 2),
+            (0, 3),
+            (512, 10),
+            (512, 10),
+            (512, 10),
+            (0, 5),
+            (0, 1),
+            (0, 4),
+            (0, 5),
+            (0, 1),
+            (0, 4),
+            (0, 5),
+            (0, 1),
+            (0, 4),
+            (1, 5),
+            (0, 1),
+            (0x1f, 5),
+            (0, 1),
+            // Partition 0 splits the block into left and right halves
+            (0, 5),
+            // Every pixel picks the second endpoint of its region, anchors have a bit less
+            (0b11, 2),
+            ((1 << 42) - 1, 42),
+            (0b11, 2),
+        ];
+        let pixels = decode_bc6h_block(&build_block(&fields), false).unwrap();
+        // Region 0 is uniform, region 1 uses z = w - 1 in red
+        assert_eq!(pixels[0], [1.5146484; 3]);
+        assert_eq!(pixels[3][0], 1.484375);
+        assert_eq!(pixels[3][1], 1.5146484);
+    }
+
+    #[test]
+    fn test_mode14_reversed_bits() {
+        // rw[10:15] is stored high bit first, so the first of those bits is rw[15]
+        let fields = [
+            (0b01111, 5),
+            (0, 30),
+            (0, 4),
+            (0b000001, 6),
+            (0, 4),
+            (0, 6),
+            (0, 4),
+            (0, 6),
+            (0, 63),
+        ];
+        let pixels = decode_bc6h_block(&build_block(&fields), false).unwrap();
+        assert_eq!(pixels[0], [1.5, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_signed() {
+        // Mode 11 with r = -1 and g = 511 as 10 bit two's complement
+        let fields = [
+            (0b00011, 5),
+            (0x3ff, 10),
+            (511, 10),
+            (0, 10),
+            (0x3ff, 10),
+            (511, 10),
+            (0, 10),
+            (0, 63),
+        ];
+        let pixels = decode_bc6h_block(&build_block(&fields), true).unwrap();
+        assert!(pixels[0][0] < 0.0);
+        assert_eq!(pixels[0][1], 65504.0);
+    }
+
+    #[test]
+    fn test_reserved_mode() {
+        let block = build_block(&[(0b10011, 5), (u64::MAX, 64), (u64::MAX, 59)]);
+        assert_eq!(decode_bc6h_block(&block, false).unwrap(), [[0.0; 3]; 16]);
+    }
+
+    #[test]
+    fn test_invalid_block_length() {
+        assert!(matches!(
+            decode_bc6h_block(&[0; 8], false),
+            Err(DecodeError::InvalidBlockLength {
+                expected: 16,
+                actual: 8
+            })
+        ));
+    }
+}
@@ -1,4 +1,15 @@
-use bitvec::{field::BitField, order::Lsb0, slice::BitSlice};
+use bitvec::{field::BitField, order::Lsb0, slice::BitSlice, view::BitView};
+
+use crate::{
+    bptc::{read_indices, FIXUP_TABLE_2, PARTITION_TABLE_2, WEIGHTS_2, WEIGHTS_3, WEIGHTS_4},
+    error::DecodeError,
+    pixel::replicate_bits,
+};
+// Re-exported so existing `bc7_unorm::RGBA` paths keep working
+pub use crate::pixel::{RGB, RGBA};
+
+/// Size of a single compressed 4x4 block in bytes
+pub const BLOCK_SIZE: usize = 16;
 
 /// https://learn.microsoft.com/en-us/windows/win32/direct3d11/bc7-format-mode-reference
 /// https://github.com/microsoft/DirectXTex/blob/main/DirectXTex/BC6HBC7.cpp
@@ -70,76 +81,7 @@ pub const PARTITION_TABLE_3: [[usize; 16]; 64] = [
     [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0], // Shape 63
 ];
 
-const PARTITION_TABLE_2: [[usize; 16]; 64] = [
-    // BC6H/BC7 Partition Set for 2 Subsets
-    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1], // Shape 0
-    [0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1], // Shape 1
-    [0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1], // Shape 2
-    [0, 0, 0, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 1, 1, 1], // Shape 3
-    [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 1, 1], // Shape 4
-    [0, 0, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1], // Shape 5
-    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1], // Shape 6
-    [0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 1], // Shape 7
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1], // Shape 8
-    [0, 0, 1, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], // Shape 9
-    [0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1], // Shape 10
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 1, 1, 1], // Shape 11
-    [0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], // Shape 12
-    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1], // Shape 13
-    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1], // Shape 14
-    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1], // Shape 15
-    [0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 1, 1, 1, 1], // Shape 16
-    [0, 1, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0], // Shape 17
-    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0], // Shape 18
-    [0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0], // Shape 19
-    [0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0], // Shape 20
-    [0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0], // Shape 21
-    [0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0], // Shape 22
-    [0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 1], // Shape 23
-    [0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0], // Shape 24
-    [0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0], // Shape 25
-    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0], // Shape 26
-    [0, 0, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 1, 1, 0, 0], // Shape 27
-    [0, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 0, 1, 0, 0, 0], // Shape 28
-    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0], // Shape 29
-    [0, 1, 1, 1, 0, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 0], // Shape 30
-    [0, 0, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0, 0], // Shape 31
-    // BC7 Partition Set for 2 Subsets (second-half)
-    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1], // Shape 32
-    [0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1], // Shape 33
-    [0, 1, 0, 1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 0, 1, 0], // Shape 34
-    [0, 0, 1, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0], // Shape 35
-    [0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 1, 1, 0, 0], // Shape 36
-    [0, 1, 0, 1, 0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 1, 0], // Shape 37
-    [0, 1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 0, 1, 0, 0, 1], // Shape 38
-    [0, 1, 0, 1, 1, 0, 1, 0, 1, 0, 1, 0, 0, 1, 0, 1], // Shape 39
-    [0, 1, 1, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 1, 0], // Shape 40
-    [0, 0, 0, 1, 0, 0, 1, 1, 1, 1, 0, 0, 1, 0, 0, 0], // Shape 41
-    [0, 0, 1, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 1, 0, 0], // Shape 42
-    [0, 0, 1, 1, 1, 0, 1, 1, 1, 1, 0, 1, 1, 1, 0, 0], // Shape 43
-    [0, 1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 1, 0, 1, 1, 0], // Shape 44
-    [0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 1, 1], // Shape 45
-    [0, 1, 1, 0, 0, 1, 1, 0, 1, 0, 0, 1, 1, 0, 0, 1], // Shape 46
-    [0, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 0], // Shape 47
-    [0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0], // Shape 48
-    [0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 0], // Shape 49
-    [0, 0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0], // Shape 50
-    [0, 0, 0, 0, 0, 1, 0, 0, 1, 1, 1, 0, 0, 1, 0, 0], // Shape 51
-    [0, 1, 1, 0, 1, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 1], // Shape 52
-    [0, 0, 1, 1, 0, 1, 1, 0, 1, 1, 0, 0, 1, 0, 0, 1], // Shape 53
-    [0, 1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0], // Shape 54
-    [0, 0, 1, 1, 1, 0, 0, 1, 1, 1, 0, 0, 0, 1, 1, 0], // Shape 55
-    [0, 1, 1, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 0, 0, 1], // Shape 56
-    [0, 1, 1, 0, 0, 0, 1, 1, 0, 0, 1, 1, 1, 0, 0, 1], // Shape 57
-    [0, 1, 1, 1, 1, 1, 1, 0, 1, 0, 0, 0, 0, 0, 0, 1], // Shape 58
-    [0, 0, 0, 1, 1, 0, 0, 0, 1, 1, 1, 0, 0, 1, 1, 1], // Shape 59
-    [0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1], // Shape 60
-    [0, 0, 1, 1, 0, 0, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0], // Shape 61
-    [0, 0, 1, 0, 0, 0, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0], // Shape 62
-    [0, 1, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 1, 1, 1], // Shape 63
-];
-
-const FIXUP_TABLE: [[usize; 3]; 64] = [
+const FIXUP_TABLE_3: [[usize; 3]; 64] = [
     // BC7 Partition Set Fixups for 3 Subsets
     [0, 3, 15],
     [0, 3, 8],
@@ -207,496 +149,392 @@ const FIXUP_TABLE: [[usize; 3]; 64] = [
     [0, 3, 8],
 ];
 
-#[derive(Debug, Copy, Clone)]
-pub struct RGB {
-    pub r: u8,
-    pub g: u8,
-    pub b: u8,
-}
-
 #[derive(Debug)]
 pub struct RGBInterval {
-    pub min: RGB,
-    pub max: RGB,
+    pub start: RGB,
+    pub end: RGB,
 }
 
 impl RGBInterval {
     /// Sample within the interval
-    /// x should represent where to sample in the 0-255 range
-    pub fn lerp(&self, t: u8) -> RGB {
+    /// index is an index_bits wide value, where 0 gives start and the max gives end
+    pub fn lerp(&self, index: u8, index_bits: usize) -> RGB {
         RGB {
-            r: interpolate_u8(self.min.r, self.max.r, t),
-            g: interpolate_u8(self.min.g, self.max.g, t),
-            b: interpolate_u8(self.min.b, self.max.b, t),
+            r: interpolate(self.start.r, self.end.r, index, index_bits),
+            g: interpolate(self.start.g, self.end.g, index, index_bits),
+            b: interpolate(self.start.b, self.end.b, index, index_bits),
         }
     }
 }
 
-/// Fast integer interpolation
-fn interpolate_u8(min: u8, max: u8, t: u8) -> u8 {
-    let (low, high) = if min < max { (min, max) } else { (max, min) };
-    low + ((high - low) as u16 * t as u16 / 255) as u8
-}
-
-pub fn decode_block_mode_0(block: &BitSlice<u8, Lsb0>) -> [RGB; 16] {
-    assert_eq!(block.len(), 128);
-
-    let mode = block.first_one().expect("No valid BC7 mode found");
-    assert_eq!(mode, 0);
-
-    // =========================== Constants for this mode
-    const RGB_BITS: usize = 4;
-    const P_BITS: usize = 1;
-    const PARTITION_BITS: usize = 4;
-    const RGB_INTERVALS: usize = 3;
-    const INDEX_BITS: usize = 3;
-
-    const PARTITION_OFFSET: usize = 1;
-    const RGBS_OFFSET: usize = PARTITION_OFFSET + PARTITION_BITS;
-    const PS_OFFSET: usize = RGBS_OFFSET + RGB_INTERVALS * 2 * 3 * RGB_BITS;
-    const INDICES_OFFSET: usize = PS_OFFSET + RGB_INTERVALS * 2 * P_BITS;
-    //println!("{} {} {}", RGBS_OFFSET, PS_OFFSET, INDICES_OFFSET);
-
-    // =========================== Step 1: Go from raw bits to raw native dtypes
-
-    // Table selection
-    let partition = block[PARTITION_OFFSET..PARTITION_OFFSET + PARTITION_BITS].load::<usize>();
-    let partition_table = PARTITION_TABLE_3[partition];
-    //println!("partition: {} {:?}", partition, partition_table);
-    let fixup_table = FIXUP_TABLE[partition];
-    //println!("fixup {:?}", fixup_table);
-
-    // Rx6 Gx6 Bx6
-    let ps = &block[PS_OFFSET..INDICES_OFFSET];
-    let rgbs = block[RGBS_OFFSET..PS_OFFSET]
-        .chunks_exact(RGB_BITS)
-        .map(|c| c.load::<u8>())
-        .enumerate()
-        // Shift by common offset
-        .map(|(i, rgb)| {
-            if ps[i % (2 * RGB_INTERVALS)] {
-                rgb << P_BITS
-            } else {
-                rgb
-            }
-        })
-        // Shift from 5 bit to 8 bit range
-        .map(|rgb| rgb << (8 - RGB_BITS - P_BITS))
-        .collect::<Vec<_>>();
-    //println!("rgbs: {:?}", rgbs);
-
-    // Indices into intervals
-    let mut indices = [0; 16];
-    let index_block = &block[INDICES_OFFSET..];
-    let mut offset = 0;
-    indices.iter_mut().enumerate().for_each(|(i, x)| {
-        let width = if fixup_table.contains(&i) {
-            INDEX_BITS - 1
-        } else {
-            INDEX_BITS
-        };
-        *x = index_block[offset..offset + width].load::<u8>();
-        offset += width;
-    });
-    //println!("indices: {:?}", indices);
-
-    // ========================= Step 2: Prepare RGB lookup structs
-    let rgb_intervals: [RGBInterval; RGB_INTERVALS] = (0..2 * RGB_INTERVALS)
-        .map(|i| RGB {
-            r: rgbs[i],
-            g: rgbs[i + 2 * RGB_INTERVALS],
-            b: rgbs[i + 2 * 2 * RGB_INTERVALS],
-        })
-        .array_chunks::<2>()
-        .map(|[min, max]| RGBInterval { min, max })
-        .collect::<Vec<_>>()
-        // No way to directly collect into an array :(
-        .try_into()
-        .unwrap();
+/// Bit-exact BC7 interpolation between two endpoints, matching the reference decoder
+fn interpolate(e0: u8, e1: u8, index: u8, index_bits: usize) -> u8 {
+    let weight = match index_bits {
+        2 => WEIGHTS_2[index as usize],
+        3 => WEIGHTS_3[index as usize],
+        4 => WEIGHTS_4[index as usize],
+        _ => unreachable!("BC7 only uses 2, 3 or 4 bit indices"),
+    };
 
-    //println!("parsed rgbs: {:?}", rgb_intervals);
-
-    // ======================== Step 3: Lerp em!
-    let pixels = partition_table
-        .into_iter()
-        .zip(indices)
-        .map(|(table, index)| rgb_intervals[table].lerp(index))
-        .collect::<Vec<RGB>>()
-        // No way to directly collect into an array :(
-        .try_into()
-        .unwrap();
-    //println!("Decoded: {:?}", pixels);
-
-    pixels
+    (((64 - weight) * e0 as u16 + weight * e1 as u16 + 32) >> 6) as u8
 }
 
-pub fn decode_block_mode_1(block: &BitSlice<u8, Lsb0>) -> [RGB; 16] {
-    assert_eq!(block.len(), 128);
-
-    let mode = block.first_one().expect("No valid BC7 mode found");
-    assert_eq!(mode, 1);
-
-    // =========================== Constants for this mode
-    const RGB_BITS: usize = 6;
-    const P_BITS: usize = 1;
-    const PARTITION_BITS: usize = 6;
-    const RGB_INTERVALS: usize = 2;
-    const INDEX_BITS: usize = 3;
-
-    const PARTITION_OFFSET: usize = 2;
-    const RGBS_OFFSET: usize = PARTITION_OFFSET + PARTITION_BITS;
-    const PS_OFFSET: usize = RGBS_OFFSET + RGB_INTERVALS * 2 * 3 * RGB_BITS;
-    const INDICES_OFFSET: usize = PS_OFFSET + RGB_INTERVALS * P_BITS;
-    //println!("{} {} {}", RGBS_OFFSET, PS_OFFSET, INDICES_OFFSET);
-
-    // =========================== Step 1: Go from raw bits to raw native dtypes
-
-    // Table selection
-    let partition = block[PARTITION_OFFSET..PARTITION_OFFSET + PARTITION_BITS].load::<usize>();
-    let partition_table = PARTITION_TABLE_2[partition];
-    //println!("partition: {} {:?}", partition, partition_table);
-    let fixup_table = FIXUP_TABLE[partition];
-    //println!("fixup {:?}", fixup_table);
-
-    // Rx6 Gx6 Bx6
-    let ps = &block[PS_OFFSET..INDICES_OFFSET];
-    let rgbs = block[RGBS_OFFSET..PS_OFFSET]
-        .chunks_exact(RGB_BITS)
-        .map(|c| c.load::<u8>())
-        .enumerate()
-        // Shift by common offset
-        .map(|(i, rgb)| {
-            if ps[(i / 2) % RGB_INTERVALS] {
-                rgb << P_BITS
-            } else {
-                rgb
-            }
-        })
-        // Shift from 5 bit to 8 bit range
-        .map(|rgb| rgb << (8 - RGB_BITS - P_BITS))
-        .collect::<Vec<_>>();
-    //println!("rgbs: {:?}", rgbs);
-
-    // Indices into intervals
-    let mut indices = [0; 16];
-    let index_block = &block[INDICES_OFFSET..];
-    let mut offset = 0;
-    indices.iter_mut().enumerate().for_each(|(i, x)| {
-        let width = if fixup_table.contains(&i) {
-            INDEX_BITS - 1
-        } else {
-            INDEX_BITS
-        };
-        *x = index_block[offset..offset + width].load::<u8>();
-        offset += width;
-    });
-    //println!("indices: {:?}", indices);
-
-    // ========================= Step 2: Prepare RGB lookup structs
-    let rgb_intervals: [RGBInterval; RGB_INTERVALS] = (0..2 * RGB_INTERVALS)
-        .map(|i| RGB {
-            r: rgbs[i],
-            g: rgbs[i + 2 * RGB_INTERVALS],
-            b: rgbs[i + 2 * 2 * RGB_INTERVALS],
-        })
-        .array_chunks::<2>()
-        .map(|[min, max]| RGBInterval { min, max })
-        .collect::<Vec<_>>()
-        // No way to directly collect into an array :(
-        .try_into()
-        .unwrap();
-
-    //println!("parsed rgbs: {:?}", rgb_intervals);
-
-    // ======================== Step 3: Lerp em!
-    let pixels = partition_table
-        .into_iter()
-        .zip(indices)
-        .map(|(table, index)| rgb_intervals[table].lerp(index))
-        .collect::<Vec<RGB>>()
-        // No way to directly collect into an array :(
-        .try_into()
-        .unwrap();
-    //println!("Decoded: {:?}", pixels);
-
-    pixels
+/// How p-bits are assigned to endpoints
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum PBits {
+    None,
+    /// One p-bit per subset, shared by both of its endpoints
+    Shared,
+    /// One p-bit per endpoint
+    Unique,
 }
 
-fn print_bits(bits: &BitSlice<u8, Lsb0>) {
-    let out = bits
-        .iter()
-        .map(|x| if *x { 'X' } else { '-' })
-        .collect::<String>();
-
-    println!("{}", out);
+/// Bit layout of a single BC7 mode, as given in the mode reference table
+#[derive(Debug)]
+struct ModeInfo {
+    subsets: usize,
+    partition_bits: usize,
+    rotation_bits: usize,
+    index_selection_bits: usize,
+    color_bits: usize,
+    alpha_bits: usize,
+    p_bits: PBits,
+    index_bits: usize,
+    /// Width of the separate alpha index set, 0 if alpha shares the colour indices
+    index_bits_2: usize,
 }
 
-pub fn decode_block_mode_2(block: &BitSlice<u8, Lsb0>) -> [RGB; 16] {
-    assert_eq!(block.len(), 128);
-
-    let mode = block.first_one().expect("No valid BC7 mode found");
-    assert_eq!(mode, 2);
+#[rustfmt::skip]
+const MODES: [ModeInfo; 8] = [
+    ModeInfo { subsets: 3, partition_bits: 4, rotation_bits: 0, index_selection_bits: 0, color_bits: 4, alpha_bits: 0, p_bits: PBits::Unique, index_bits: 3, index_bits_2: 0 },
+    ModeInfo { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 6, alpha_bits: 0, p_bits: PBits::Shared, index_bits: 3, index_bits_2: 0 },
+    ModeInfo { subsets: 3, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 0, p_bits: PBits::None,   index_bits: 2, index_bits_2: 0 },
+    ModeInfo { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 0, p_bits: PBits::Unique, index_bits: 2, index_bits_2: 0 },
+    ModeInfo { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 1, color_bits: 5, alpha_bits: 6, p_bits: PBits::None,   index_bits: 2, index_bits_2: 3 },
+    ModeInfo { subsets: 1, partition_bits: 0, rotation_bits: 2, index_selection_bits: 0, color_bits: 7, alpha_bits: 8, p_bits: PBits::None,   index_bits: 2, index_bits_2: 2 },
+    ModeInfo { subsets: 1, partition_bits: 0, rotation_bits: 0, index_selection_bits: 0, color_bits: 7, alpha_bits: 7, p_bits: PBits::Unique, index_bits: 4, index_bits_2: 0 },
+    ModeInfo { subsets: 2, partition_bits: 6, rotation_bits: 0, index_selection_bits: 0, color_bits: 5, alpha_bits: 5, p_bits: PBits::Unique, index_bits: 2, index_bits_2: 0 },
+];
 
-    // =========================== Constants for this mode
-    const RGB_BITS: usize = 5;
-    const PARTITION_BITS: usize = 6;
-    const RGB_INTERVALS: usize = 3;
-    const INDEX_BITS: usize = 2;
+/// Decode a single 16 byte BC7 block into a row-major 4x4 grid of pixels
+pub fn decode_bc7_block(block: &[u8]) -> Result<[RGBA; 16], DecodeError> {
+    if block.len() != BLOCK_SIZE {
+        return Err(DecodeError::InvalidBlockLength {
+            expected: BLOCK_SIZE,
+            actual: block.len(),
+        });
+    }
+    let block = block.view_bits::<Lsb0>();
+
+    // Mode is given by the number of leading zeros in the first byte
+    let pixels = match block.first_one() {
+        Some(mode) if mode < MODES.len() => decode_block(block, mode),
+        // Reserved mode, the spec says to output transparent black
+        _ => {
+            [RGBA {
+                r: 0,
+                g: 0,
+                b: 0,
+                a: 0,
+            }; 16]
+        }
+    };
 
-    const PARTITION_OFFSET: usize = 3;
-    const RGBS_OFFSET: usize = PARTITION_OFFSET + PARTITION_BITS;
-    const INDICES_OFFSET: usize = RGBS_OFFSET + RGB_INTERVALS * 2 * 3 * RGB_BITS;
-    println!("{} {}", RGBS_OFFSET, INDICES_OFFSET);
+    Ok(pixels)
+}
 
-    print_bits(&block[..PARTITION_OFFSET]);
-    print_bits(&block[PARTITION_OFFSET..RGBS_OFFSET]);
-    print_bits(&block[RGBS_OFFSET..INDICES_OFFSET]);
-    print_bits(&block[INDICES_OFFSET..]);
+fn decode_block(block: &BitSlice<u8, Lsb0>, mode: usize) -> [RGBA; 16] {
+    let info = &MODES[mode];
+    let num_endpoints = info.subsets * 2;
+    let num_ps = match info.p_bits {
+        PBits::None => 0,
+        PBits::Shared => info.subsets,
+        PBits::Unique => num_endpoints,
+    };
 
     // =========================== Step 1: Go from raw bits to raw native dtypes
 
-    // Table selection
-    let partition = block[PARTITION_OFFSET..PARTITION_OFFSET + PARTITION_BITS].load::<usize>();
-    let partition_table = PARTITION_TABLE_3[partition];
-    println!("partition: {} {:?}", partition, partition_table);
-    let fixup_table = FIXUP_TABLE[partition];
-    println!("fixup {:?}", fixup_table);
-
-    // Rx6 Gx6 Bx6
-    let rgbs = block[RGBS_OFFSET..INDICES_OFFSET]
-        .chunks_exact(RGB_BITS)
-        .map(|c| c.load::<u8>())
-        // Shift from 5 bit to 8 bit range
-        .map(|rgb| rgb << (8 - RGB_BITS))
-        .collect::<Vec<_>>();
-    println!("rgbs: {:?}", rgbs);
-
-    // Indices into intervals
-    let mut indices = [0; 16];
-    let index_block = &block[INDICES_OFFSET..];
-    let mut offset = 0;
-    indices.iter_mut().enumerate().for_each(|(i, x)| {
-        let width = if fixup_table.contains(&i) {
-            INDEX_BITS - 1
-        } else {
-            INDEX_BITS
-        };
-        *x = index_block[offset..offset + width].load::<u8>();
+    // Fields are packed back to back after the mode bits
+    let mut offset = mode + 1;
+    let mut next_field = |width: usize| {
+        let field = &block[offset..offset + width];
         offset += width;
-    });
-    println!("indices: {:?}", indices);
+        field
+    };
+
+    let partition = load_optional(next_field(info.partition_bits));
+    let rotation = load_optional(next_field(info.rotation_bits));
+    let index_selection = load_optional(next_field(info.index_selection_bits));
+    let rgb_bits = next_field(num_endpoints * 3 * info.color_bits);
+    let alpha_bits = next_field(num_endpoints * info.alpha_bits);
+    let ps = next_field(num_ps);
+    trace!(
+        "mode {mode}: partition {partition}, rotation {rotation}, index selection {index_selection}"
+    );
+    trace!(
+        "raw rgb {}, alpha {}, p {}",
+        fmt_bits(rgb_bits),
+        fmt_bits(alpha_bits),
+        fmt_bits(ps)
+    );
+
+    // Table selection
+    let (partition_table, anchors): ([usize; 16], &[usize]) = match info.subsets {
+        1 => ([0; 16], &[0]),
+        2 => (PARTITION_TABLE_2[partition], &FIXUP_TABLE_2[partition]),
+        _ => (PARTITION_TABLE_3[partition], &FIXUP_TABLE_3[partition]),
+    };
+
+    // Rx(2*subsets) Gx(2*subsets) Bx(2*subsets) [Ax(2*subsets)]
+    let rgbs = read_endpoints(rgb_bits, info.color_bits, num_endpoints, ps);
+    let alphas = (info.alpha_bits > 0)
+        .then(|| read_endpoints(alpha_bits, info.alpha_bits, num_endpoints, ps));
+
+    // Indices into intervals, with an optional second set for alpha
+    let index_field = next_field(16 * info.index_bits - info.subsets);
+    let mut rgb_indices = (
+        read_indices(index_field, anchors, info.index_bits),
+        info.index_bits,
+    );
+    let mut alpha_indices = if info.index_bits_2 > 0 {
+        let index_field_2 = next_field(16 * info.index_bits_2 - 1);
+        (
+            read_indices(index_field_2, &[0], info.index_bits_2),
+            info.index_bits_2,
+        )
+    } else {
+        rgb_indices
+    };
+    if index_selection == 1 {
+        std::mem::swap(&mut rgb_indices, &mut alpha_indices);
+    }
+    trace!("endpoints rgb {rgbs:?}, alpha {alphas:?}");
+    trace!(
+        "indices rgb {:?}, alpha {:?}",
+        rgb_indices.0,
+        alpha_indices.0
+    );
 
     // ========================= Step 2: Prepare RGB lookup structs
-    let rgb_intervals: [RGBInterval; RGB_INTERVALS] = (0..2 * RGB_INTERVALS)
+    let rgb_intervals = (0..num_endpoints)
         .map(|i| RGB {
             r: rgbs[i],
-            g: rgbs[i + 2 * RGB_INTERVALS],
-            b: rgbs[i + 2 * 2 * RGB_INTERVALS],
+            g: rgbs[i + num_endpoints],
+            b: rgbs[i + 2 * num_endpoints],
         })
         .array_chunks::<2>()
-        .map(|[min, max]| RGBInterval { min, max })
-        .collect::<Vec<_>>()
-        // No way to directly collect into an array :(
-        .try_into()
-        .unwrap();
-
-    println!("parsed rgbs: {:?}", rgb_intervals);
+        .map(|[start, end]| RGBInterval { start, end })
+        .collect::<Vec<_>>();
 
     // ======================== Step 3: Lerp em!
-    let pixels = partition_table
-        .into_iter()
-        .zip(indices)
-        .map(|(table, index)| rgb_intervals[table].lerp(index))
-        .collect::<Vec<RGB>>()
+    (0..16)
+        .map(|i| {
+            let subset = partition_table[i];
+            let rgb = rgb_intervals[subset].lerp(rgb_indices.0[i], rgb_indices.1);
+            let alpha = alphas.as_ref().map_or(255, |alphas| {
+                interpolate(
+                    alphas[2 * subset],
+                    alphas[2 * subset + 1],
+                    alpha_indices.0[i],
+                    alpha_indices.1,
+                )
+            });
+            rotate(RGBA::from_rgb_alpha(rgb, alpha), rotation)
+        })
+        .collect::<Vec<_>>()
         // No way to directly collect into an array :(
         .try_into()
-        .unwrap();
-    println!("Decoded: {:?}", pixels);
-
-    pixels
+        .unwrap()
 }
 
-pub fn decode_block_mode_3(block: &BitSlice<u8, Lsb0>) -> [RGB; 16] {
-    assert_eq!(block.len(), 128);
-
-    let mode = block.first_one().expect("No valid BC7 mode found");
-    assert_eq!(mode, 3);
-
-    // =========================== Constants for this mode
-    const RGB_BITS: usize = 7;
-    const P_BITS: usize = 1;
-    const PARTITION_BITS: usize = 6;
-    const RGB_INTERVALS: usize = 2;
-    const INDEX_BITS: usize = 2;
-
-    const PARTITION_OFFSET: usize = 3;
-    const RGBS_OFFSET: usize = PARTITION_OFFSET + PARTITION_BITS;
-    const PS_OFFSET: usize = RGBS_OFFSET + RGB_INTERVALS * 2 * 3 * RGB_BITS;
-    const INDICES_OFFSET: usize = PS_OFFSET + RGB_INTERVALS * 2 * P_BITS;
-    //println!("{} {} {}", RGBS_OFFSET, PS_OFFSET, INDICES_OFFSET);
+/// Render a bit field LSB first, for tracing raw block contents
+#[cfg(feature = "trace")]
+fn fmt_bits(bits: &BitSlice<u8, Lsb0>) -> String {
+    bits.iter().map(|x| if *x { 'X' } else { '-' }).collect()
+}
 
-    // =========================== Step 1: Go from raw bits to raw native dtypes
+/// Load a field that some modes leave out entirely, giving 0 when it is absent
+fn load_optional(bits: &BitSlice<u8, Lsb0>) -> usize {
+    if bits.is_empty() {
+        0
+    } else {
+        bits.load::<usize>()
+    }
+}
 
-    // Table selection
-    let partition = block[PARTITION_OFFSET..PARTITION_OFFSET + PARTITION_BITS].load::<usize>();
-    let partition_table = PARTITION_TABLE_2[partition];
-    //println!("partition: {} {:?}", partition, partition_table);
-    let fixup_table = FIXUP_TABLE[partition];
-    //println!("fixup {:?}", fixup_table);
-
-    // Rx6 Gx6 Bx6
-    let ps = &block[PS_OFFSET..INDICES_OFFSET];
-    let rgbs = block[RGBS_OFFSET..PS_OFFSET]
-        .chunks_exact(RGB_BITS)
+/// Read channel-major endpoint values and expand them to 8 bits.
+/// ps holds either one p-bit per endpoint, one per pair of endpoints, or none at all
+fn read_endpoints(
+    bits: &BitSlice<u8, Lsb0>,
+    channel_bits: usize,
+    num_endpoints: usize,
+    ps: &BitSlice<u8, Lsb0>,
+) -> Vec<u8> {
+    bits.chunks_exact(channel_bits)
         .map(|c| c.load::<u8>())
         .enumerate()
-        // Shift by common offset
-        .map(|(i, rgb)| {
-            if ps[i % (2 * RGB_INTERVALS)] {
-                rgb << P_BITS
-            } else {
-                rgb
+        .map(|(i, value)| {
+            if ps.is_empty() {
+                return replicate_bits(value as u32, channel_bits as u32, 8) as u8;
             }
-        })
-        // Shift from 5 bit to 8 bit range
-        .map(|rgb| rgb << (8 - RGB_BITS - P_BITS))
-        .collect::<Vec<_>>();
-    //println!("rgbs: {:?}", rgbs);
-
-    // Indices into intervals
-    let mut indices = [0; 16];
-    let index_block = &block[INDICES_OFFSET..];
-    let mut offset = 0;
-    indices.iter_mut().enumerate().for_each(|(i, x)| {
-        let width = if fixup_table.contains(&i) {
-            INDEX_BITS - 1
-        } else {
-            INDEX_BITS
-        };
-        *x = index_block[offset..offset + width].load::<u8>();
-        offset += width;
-    });
-    //println!("indices: {:?}", indices);
 
-    // ========================= Step 2: Prepare RGB lookup structs
-    let rgb_intervals: [RGBInterval; RGB_INTERVALS] = (0..2 * RGB_INTERVALS)
-        .map(|i| RGB {
-            r: rgbs[i],
-            g: rgbs[i + 2 * RGB_INTERVALS],
-            b: rgbs[i + 2 * 2 * RGB_INTERVALS],
+            // Append the p-bit as the new LSB
+            let p = ps[(i % num_endpoints) * ps.len() / num_endpoints] as u8;
+            replicate_bits(((value << 1) | p) as u32, channel_bits as u32 + 1, 8) as u8
         })
-        .array_chunks::<2>()
-        .map(|[min, max]| RGBInterval { min, max })
-        .collect::<Vec<_>>()
-        // No way to directly collect into an array :(
-        .try_into()
-        .unwrap();
-
-    //println!("parsed rgbs: {:?}", rgb_intervals);
-
-    // ======================== Step 3: Lerp em!
-    let pixels = partition_table
-        .into_iter()
-        .zip(indices)
-        .map(|(table, index)| rgb_intervals[table].lerp(index))
-        .collect::<Vec<RGB>>()
-        // No way to directly collect into an array :(
-        .try_into()
-        .unwrap();
-    //println!("Decoded: {:?}", pixels);
-
-    pixels
+        .collect()
 }
 
-pub fn decode_block_mode_4(block: &BitSlice<u8, Lsb0>) -> [RGB; 16] {
-    assert_eq!(block.len(), 128);
-
-    let mode = block.first_one().expect("No valid BC7 mode found");
-    assert_eq!(mode, 4);
-
-    // =========================== Constants for this mode
-    const RGB_BITS: usize = 5;
-    const A_BITS: usize = 6;
-    const RGB_INTERVALS: usize = 1;
-
-    const RGBS_OFFSET: usize = 4 + 2;
-    const AS_OFFSET: usize = RGBS_OFFSET + RGB_INTERVALS * 2 * 3 * RGB_BITS;
-    const INDICES_OFFSET: usize = AS_OFFSET + RGB_INTERVALS * 2 * A_BITS;
-
-    // =========================== Step 1: Go from raw bits to raw native dtypes
-
-    let rotation = block[4..6].load::<u8>();
-    println!("rotation: {}", rotation);
+/// Undo the channel rotation of modes 4 and 5 by swapping alpha back with a colour channel
+fn rotate(pixel: RGBA, rotation: usize) -> RGBA {
+    let RGBA { r, g, b, a } = pixel;
+    match rotation {
+        1 => RGBA { r: a, g, b, a: r },
+        2 => RGBA { r, g: a, b, a: g },
+        3 => RGBA { r, g, b: a, a: b },
+        _ => pixel,
+    }
+}
 
-    // Rx6 Gx6 Bx6
-    let rgbs = block[RGBS_OFFSET..AS_OFFSET]
-        .chunks_exact(RGB_BITS)
-        .map(|c| c.load::<u8>())
-        // Shift from 5 bit to 8 bit range
-        .map(|rgb| rgb << (8 - RGB_BITS))
-        .collect::<Vec<_>>();
-    println!("rgbs: {:?}", rgbs);
+#[cfg(test)]
+mod tests {
+    use super::{decode_bc7_block, interpolate, PBits, MODES, RGBA};
+    use crate::{error::DecodeError, test_util::build_block};
 
-    let alphas = block[AS_OFFSET..INDICES_OFFSET]
-        .chunks_exact(A_BITS)
-        .map(|c| c.load::<u8>())
-        // Shift from 5 bit to 8 bit range
-        .map(|rgb| rgb << (8 - A_BITS))
-        .collect::<Vec<_>>();
-    println!("alphas: {:?}", alphas);
-
-    // Indices into intervals
-    let index_bits = if block[6] { 3 } else { 2 };
-    let indices = block[INDICES_OFFSET..]
-        .chunks_exact(index_bits)
-        .take(16)
-        .map(|chunk| chunk.load::<u8>())
-        .map(|rgb| rgb << (8 - index_bits))
-        .collect::<Vec<_>>();
+    #[test]
+    fn test_interp() {
+        assert_eq!(interpolate(0, 255, 0, 2), 0);
+        assert_eq!(interpolate(0, 255, 3, 2), 255);
+        assert_eq!(interpolate(0, 255, 1, 2), 84);
+        assert_eq!(interpolate(0, 255, 7, 4), 120);
+        assert_eq!(interpolate(32, 64, 4, 3), 51);
+        // Endpoint order is kept, so a descending interval runs backwards
+        assert_eq!(interpolate(255, 0, 0, 3), 255);
+        assert_eq!(interpolate(255, 0, 1, 3), 219);
+    }
 
-    println!("indices: {:?}", indices);
+    #[test]
+    fn test_mode0() {}
 
-    // ========================= Step 2: Prepare RGB lookup structs
-    let rgb_intervals: [RGBInterval; RGB_INTERVALS] = (0..2 * RGB_INTERVALS)
-        .map(|i| RGB {
-            r: rgbs[i],
-            g: rgbs[i + 2 * RGB_INTERVALS],
-            b: rgbs[i + 2 * 2 * RGB_INTERVALS],
-        })
-        .array_chunks::<2>()
-        .map(|[min, max]| RGBInterval { min, max })
-        .collect::<Vec<_>>()
-        // No way to directly collect into an array :(
-        .try_into()
-        .unwrap();
+    #[test]
+    fn test_mode1_anchors() {
+        // Partition 17 has its second anchor at pixel 2, where the 3 subset table would say 3
+        let channel = [(0, 6), (0, 6), (0, 6), (63, 6)];
+        let mut fields = vec![(0b10, 2), (17, 6)];
+        (0..3).for_each(|_| fields.extend(channel));
+        // Shared p-bits, only set for subset 1
+        fields.push((0b10, 2));
+        fields.push(((1 << 46) - 1, 46));
+
+        let pixels = decode_bc7_block(&build_block(&fields)).unwrap();
+        let reds = pixels.map(|p| p.r);
+        assert_eq!(
+            reds,
+            [0, 255, 109, 255, 0, 0, 0, 255, 0, 0, 0, 0, 0, 0, 0, 0]
+        );
+    }
 
-    //println!("parsed rgbs: {:?}", rgb_intervals);
+    #[test]
+    fn test_mode_layouts() {
+        // Every mode has to fill exactly 128 bits
+        MODES.iter().enumerate().for_each(|(mode, info)| {
+            let num_endpoints = info.subsets * 2;
+            let num_ps = match info.p_bits {
+                PBits::None => 0,
+                PBits::Shared => info.subsets,
+                PBits::Unique => num_endpoints,
+            };
+            let index_bits_2 = if info.index_bits_2 > 0 {
+                16 * info.index_bits_2 - 1
+            } else {
+                0
+            };
+            let total = mode
+                + 1
+                + info.partition_bits
+                + info.rotation_bits
+                + info.index_selection_bits
+                + num_endpoints * (3 * info.color_bits + info.alpha_bits)
+                + num_ps
+                + 16 * info.index_bits
+                - info.subsets
+                + index_bits_2;
+            assert_eq!(total, 128, "mode {mode}");
+        });
+    }
 
-    // ======================== Step 3: Lerp em!
-    let pixels = indices
-        .into_iter()
-        .map(|index| rgb_intervals[0].lerp(index))
-        .collect::<Vec<_>>()
-        // No way to directly collect into an array :(
-        .try_into()
-        .unwrap();
-    //println!("Decoded: {:?}", pixels);
+    #[test]
+    fn test_reserved_mode() {
+        let pixels = decode_bc7_block(&[0; 16]).unwrap();
+        let transparent_black = RGBA {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        };
+        assert_eq!(pixels, [transparent_black; 16]);
+    }
 
-    pixels
-}
+    #[test]
+    fn test_invalid_block_length() {
+        assert!(matches!(
+            decode_bc7_block(&[0x40; 15]),
+            Err(DecodeError::InvalidBlockLength {
+                expected: 16,
+                actual: 15
+            })
+        ));
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::interpolate_u8;
+    #[test]
+    fn test_opaque_modes_alpha() {
+        let block = build_block(&[(1, 1), (0, 64), (0, 63)]);
+        let pixels = decode_bc7_block(&block).unwrap();
+        pixels.iter().for_each(|p| assert_eq!(p.a, 255));
+    }
 
     #[test]
-    fn test_interp() {
-        assert_eq!(interpolate_u8(0, 255, 0), 0);
-        assert_eq!(interpolate_u8(0, 255, 255), 255);
-        assert_eq!(interpolate_u8(0, 128, 255), 128);
-        assert_eq!(interpolate_u8(32, 64, 128), 48);
+    fn test_mode6_solid() {
+        // R, G, B and A endpoints are identical, so every index gives the same colour
+        let block = build_block(&[
+            (1 << 6, 7),
+            (0x40, 7),
+            (0x40, 7),
+            (0x20, 7),
+            (0x20, 7),
+            (0x10, 7),
+            (0x10, 7),
+            (0x7f, 7),
+            (0x7f, 7),
+            (0b11, 2),
+            (0, 63),
+        ]);
+        let pixels = decode_bc7_block(&block).unwrap();
+        pixels.iter().for_each(|p| {
+            assert_eq!((p.r, p.g, p.b, p.a), (0x81, 0x41, 0x21, 0xff));
+        });
     }
 
     #[test]
-    fn test_mode0() {}
+    fn test_mode5_rotation() {
+        // Rotation 1 swaps red and alpha, so the two channels should trade endpoints
+        let block = build_block(&[
+            (1 << 5, 6),
+            (1, 2),
+            (0, 7),
+            (0, 7),
+            (0x7f, 7),
+            (0x7f, 7),
+            (0, 7),
+            (0, 7),
+            (0xc8, 8),
+            (0xc8, 8),
+            (0, 31),
+            (0, 31),
+        ]);
+        let pixels = decode_bc7_block(&block).unwrap();
+        pixels.iter().for_each(|p| {
+            assert_eq!((p.r, p.g, p.b, p.a), (0xc8, 0xff, 0, 0));
+        });
+    }
 }
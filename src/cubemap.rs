@@ -0,0 +1,134 @@
+use image::{imageops, RgbaImage};
+
+/// Faces of a cube map, in the order they are stored in a DDS file
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX,
+        CubeFace::NegativeX,
+        CubeFace::PositiveY,
+        CubeFace::NegativeY,
+        CubeFace::PositiveZ,
+        CubeFace::NegativeZ,
+    ];
+
+    /// Short name, used to suffix the file names of separately exported faces
+    pub fn label(&self) -> &'static str {
+        match self {
+            CubeFace::PositiveX => "+x",
+            CubeFace::NegativeX => "-x",
+            CubeFace::PositiveY => "+y",
+            CubeFace::NegativeY => "-y",
+            CubeFace::PositiveZ => "+z",
+            CubeFace::NegativeZ => "-z",
+        }
+    }
+}
+
+/// How the six faces of a cube map are arranged when exported
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CubeLayout {
+    /// One 4x3 face image:
+    /// ```text
+    ///       +Y
+    ///   -X  +Z  +X  -Z
+    ///       -Y
+    /// ```
+    HorizontalCross,
+    /// One 1x6 face image, faces top to bottom in storage order
+    VerticalStrip,
+    /// Six images in storage order
+    Separate,
+}
+
+/// Arrange six equally sized faces, given in storage order, into the requested layout
+pub fn layout_faces(faces: Vec<RgbaImage>, layout: CubeLayout) -> Vec<RgbaImage> {
+    assert_eq!(faces.len(), 6, "A cube map has exactly six faces");
+    let (width, height) = faces[0].dimensions();
+
+    // Position of each face in units of faces
+    let positions: [(u32, u32); 6] = match layout {
+        CubeLayout::Separate => return faces,
+        CubeLayout::HorizontalCross => [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)],
+        CubeLayout::VerticalStrip => [(0, 0), (0, 1), (0, 2), (0, 3), (0, 4), (0, 5)],
+    };
+    let columns = positions.iter().map(|(x, _)| x + 1).max().unwrap();
+    let rows = positions.iter().map(|(_, y)| y + 1).max().unwrap();
+
+    let mut img = RgbaImage::new(columns * width, rows * height);
+    faces.iter().zip(positions).for_each(|(face, (x, y))| {
+        imageops::replace(&mut img, face, (x * width) as i64, (y * height) as i64);
+    });
+
+    vec![img]
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, RgbaImage};
+
+    use super::{layout_faces, CubeLayout};
+
+    /// 2x2 faces, each a solid colour given by its index
+    fn faces() -> Vec<RgbaImage> {
+        (0..6)
+            .map(|i| RgbaImage::from_pixel(2, 2, Rgba([i * 40, 0, 0, 255])))
+            .collect()
+    }
+
+    #[test]
+    fn test_horizontal_cross() {
+        let imgs = layout_faces(faces(), CubeLayout::HorizontalCross);
+        assert_eq!(imgs.len(), 1);
+        let img = &imgs[0];
+        assert_eq!(img.dimensions(), (8, 6));
+
+        // Face index at the top left of each 2x2 cell, gaps are left transparent
+        let cells = (0..3)
+            .flat_map(|y| (0..4).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let p = img.get_pixel(x * 2, y * 2);
+                (p[3] == 255).then_some(p[0] / 40)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            cells,
+            [
+                None,
+                Some(2),
+                None,
+                None,
+                Some(1),
+                Some(4),
+                Some(0),
+                Some(5),
+                None,
+                Some(3),
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn test_vertical_strip() {
+        let imgs = layout_faces(faces(), CubeLayout::VerticalStrip);
+        assert_eq!(imgs[0].dimensions(), (2, 12));
+        (0..6).for_each(|i| assert_eq!(imgs[0].get_pixel(1, i * 2 + 1)[0], i as u8 * 40));
+    }
+
+    #[test]
+    fn test_separate() {
+        let imgs = layout_faces(faces(), CubeLayout::Separate);
+        assert_eq!(imgs.len(), 6);
+    }
+}
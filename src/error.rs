@@ -0,0 +1,83 @@
+use std::fmt;
+
+use ddsfile::DxgiFormat;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    /// A compressed block was not the size its format requires
+    InvalidBlockLength { expected: usize, actual: usize },
+    /// The DDS file itself could not be parsed
+    Dds(ddsfile::Error),
+    /// There is no decoder for this format, or it could not be determined
+    UnsupportedFormat(Option<DxgiFormat>),
+    /// The requested layer or mip level does not exist
+    OutOfBounds { layer: u32, level: u32 },
+    /// The requested depth slice does not exist at this mip level
+    SliceOutOfBounds { slice: u32, depth: u32 },
+    /// The file has less pixel data than its header describes
+    TruncatedData { expected: usize, actual: usize },
+    /// The header's row pitch isn't the top level's rows padded to 1, 2 or 4 bytes,
+    /// and the data is too short for it to be ignored
+    UnsupportedPitch { pitch: u32, row_size: usize },
+    /// A decoded image could not be written out
+    Image(image::ImageError),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::InvalidBlockLength { expected, actual } => {
+                write!(
+                    f,
+                    "invalid block length: expected {expected} bytes, got {actual}"
+                )
+            }
+            DecodeError::Dds(e) => write!(f, "failed to read DDS: {e}"),
+            DecodeError::UnsupportedFormat(Some(format)) => {
+                write!(f, "unsupported format: {format:?}")
+            }
+            DecodeError::UnsupportedFormat(None) => write!(f, "unknown format"),
+            DecodeError::OutOfBounds { layer, level } => {
+                write!(f, "layer {layer}, mip level {level} is out of bounds")
+            }
+            DecodeError::SliceOutOfBounds { slice, depth } => {
+                write!(f, "slice {slice} is out of bounds for a depth of {depth}")
+            }
+            DecodeError::TruncatedData { expected, actual } => {
+                write!(
+                    f,
+                    "truncated pixel data: expected {expected} bytes, got {actual}"
+                )
+            }
+            DecodeError::UnsupportedPitch { pitch, row_size } => {
+                write!(
+                    f,
+                    "unsupported row pitch: {pitch} bytes for rows of {row_size} bytes"
+                )
+            }
+            DecodeError::Image(e) => write!(f, "failed to write image: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DecodeError::Dds(e) => Some(e),
+            DecodeError::Image(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<ddsfile::Error> for DecodeError {
+    fn from(e: ddsfile::Error) -> Self {
+        DecodeError::Dds(e)
+    }
+}
+
+impl From<image::ImageError> for DecodeError {
+    fn from(e: image::ImageError) -> Self {
+        DecodeError::Image(e)
+    }
+}
@@ -0,0 +1,85 @@
+use image::{Rgb, Rgb32FImage, RgbImage};
+
+/// Curve used to bring linear HDR values into the displayable [0, 1] range
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Tonemap {
+    /// Cut off everything above 1.0
+    Clamp,
+    /// x / (1 + x), keeps detail in highlights at the cost of contrast
+    #[default]
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve
+    Aces,
+}
+
+/// Options for converting HDR images into 8-bit sRGB ones, e.g. for saving as PNG
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+pub struct HdrOptions {
+    /// Exposure adjustment in stops, each one doubles the brightness
+    pub exposure: f32,
+    pub tonemap: Tonemap,
+}
+
+impl HdrOptions {
+    /// Expose, tonemap and sRGB encode a single linear colour
+    pub fn apply(&self, rgb: [f32; 3]) -> [u8; 3] {
+        let scale = self.exposure.exp2();
+        rgb.map(|v| {
+            // Negative values from signed formats have no displayable meaning
+            let v = (v * scale).max(0.0);
+            let mapped = match self.tonemap {
+                Tonemap::Clamp => v,
+                Tonemap::Reinhard => v / (1.0 + v),
+                Tonemap::Aces => (v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14),
+            };
+
+            (linear_to_srgb(mapped.clamp(0.0, 1.0)) * 255.0).round() as u8
+        })
+    }
+}
+
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.0031308 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Convert a linear HDR image into an 8-bit sRGB one
+pub fn tonemap_image(img: &Rgb32FImage, options: HdrOptions) -> RgbImage {
+    RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        Rgb(options.apply(img.get_pixel(x, y).0))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HdrOptions, Tonemap};
+
+    #[test]
+    fn test_tonemap() {
+        let clamp = HdrOptions {
+            exposure: 0.0,
+            tonemap: Tonemap::Clamp,
+        };
+        assert_eq!(clamp.apply([0.0, 1.0, 100.0]), [0, 255, 255]);
+        assert_eq!(clamp.apply([-1.0, 0.5, 0.0031308]), [0, 188, 10]);
+
+        // Reinhard maps 1.0 to 0.5, as does one stop down with clamping
+        let reinhard = HdrOptions::default();
+        let darker = HdrOptions {
+            exposure: -1.0,
+            ..clamp
+        };
+        assert_eq!(reinhard.apply([1.0; 3]), darker.apply([1.0; 3]));
+        assert!(reinhard.apply([10.0; 3])[0] < 255);
+
+        let aces = HdrOptions {
+            exposure: 0.0,
+            tonemap: Tonemap::Aces,
+        };
+        assert_eq!(aces.apply([0.0; 3]), [0; 3]);
+        assert_eq!(aces.apply([1000.0; 3]), [255; 3]);
+    }
+}
@@ -0,0 +1,415 @@
+use ddsfile::{DxgiFormat, FourCC, PixelFormat, PixelFormatFlags};
+
+use crate::pixel::{replicate_bits, RGBA};
+
+/// "BC5U", missing from ddsfile's FourCC constants
+const BC5_UNORM_ALT: u32 = 0x55354342;
+
+/// DDPF_PALETTEINDEXED8, which ddsfile drops when reading the header
+pub const PALETTE_INDEXED_8: u32 = 0x20;
+
+/// Number of RGBA entries in the palette of a palettized file
+pub const PALETTE_LEN: usize = 256;
+
+/// Legacy luminance, palettized and bitmask formats, which have no DXGI equivalent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyFormat {
+    L8,
+    L16,
+    /// Luminance in the low byte, alpha in the high byte
+    A8L8,
+    /// Luminance in the low nibble, alpha in the high nibble
+    A4L4,
+    /// Palette index
+    P8,
+    /// Palette index in the low byte, alpha in the high byte
+    A8P8,
+    /// Any other uncompressed RGB layout, e.g. R3G3B2 or A2R10G10B10
+    Masked(BitMasks),
+}
+
+impl LegacyFormat {
+    /// Bytes per pixel
+    pub fn pixel_size(self) -> usize {
+        match self {
+            LegacyFormat::L8 | LegacyFormat::A4L4 | LegacyFormat::P8 => 1,
+            LegacyFormat::L16 | LegacyFormat::A8L8 | LegacyFormat::A8P8 => 2,
+            LegacyFormat::Masked(masks) => masks.pixel_size(),
+        }
+    }
+
+    /// Decode a single pixel, with luminance spread across R, G and B. L16 keeps its top byte.
+    /// Palette entries are stored as R, G, B, A, and A8P8 replaces their alpha with its own
+    pub fn decode_pixel(self, p: &[u8], palette: &[RGBA]) -> RGBA {
+        let luma_alpha = |l: u8, a: u8| RGBA {
+            r: l,
+            g: l,
+            b: l,
+            a,
+        };
+        match self {
+            LegacyFormat::L8 => luma_alpha(p[0], 255),
+            LegacyFormat::L16 => luma_alpha(p[1], 255),
+            LegacyFormat::A8L8 => luma_alpha(p[0], p[1]),
+            LegacyFormat::A4L4 => luma_alpha((p[0] & 0xf) * 17, (p[0] >> 4) * 17),
+            LegacyFormat::P8 => palette[p[0] as usize],
+            LegacyFormat::A8P8 => RGBA {
+                a: p[1],
+                ..palette[p[0] as usize]
+            },
+            LegacyFormat::Masked(masks) => {
+                let [r, g, b, a] = masks.decode_channels(p, 8).map(|c| c as u8);
+                RGBA { r, g, b, a }
+            }
+        }
+    }
+}
+
+/// Channel layout of an uncompressed format described only by its bit count and masks.
+/// Each mask is expected to be one contiguous run of bits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitMasks {
+    /// 8, 16, 24 or 32
+    pub bit_count: u32,
+    /// R, G, B and A masks, 0 for channels the format doesn't store
+    pub masks: [u32; 4],
+}
+
+impl BitMasks {
+    /// Read the layout of an RGB pixel format, if its bit count is a whole number of bytes
+    /// and every mask fits within it. The alpha mask only counts with ALPHA_PIXELS set
+    pub fn from_pixel_format(pf: &PixelFormat) -> Option<Self> {
+        let bit_count = pf.rgb_bit_count?;
+        let alpha = pf
+            .a_bit_mask
+            .filter(|_| pf.flags.contains(PixelFormatFlags::ALPHA_PIXELS));
+        let masks = [pf.r_bit_mask, pf.g_bit_mask, pf.b_bit_mask, alpha].map(|m| m.unwrap_or(0));
+
+        let fits = masks.iter().all(|&m| (m as u64) >> bit_count == 0);
+        (matches!(bit_count, 8 | 16 | 24 | 32) && fits && masks.iter().any(|&m| m != 0))
+            .then_some(Self { bit_count, masks })
+    }
+
+    /// Bytes per pixel
+    pub fn pixel_size(&self) -> usize {
+        self.bit_count as usize / 8
+    }
+
+    /// Width in bits of the widest channel
+    pub fn max_channel_bits(&self) -> u32 {
+        self.masks
+            .iter()
+            .map(|&m| m.checked_shr(m.trailing_zeros()).unwrap_or(0).count_ones())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Extract the R, G, B and A channels of a little endian pixel and rescale them to
+    /// `target_bits`, at most 16. Missing channels are 0, except alpha which is opaque
+    pub fn decode_channels(&self, p: &[u8], target_bits: u32) -> [u16; 4] {
+        let mut bytes = [0; 4];
+        bytes[..self.pixel_size()].copy_from_slice(&p[..self.pixel_size()]);
+        let value = u32::from_le_bytes(bytes);
+
+        let mut channels = [0; 4];
+        channels.iter_mut().enumerate().for_each(|(i, c)| {
+            let mask = self.masks[i];
+            *c = if mask == 0 {
+                if i == 3 {
+                    u16::MAX >> (16 - target_bits)
+                } else {
+                    0
+                }
+            } else {
+                let shift = mask.trailing_zeros();
+                let bits = (mask >> shift).count_ones();
+                replicate_bits((value & mask) >> shift, bits, target_bits) as u16
+            };
+        });
+
+        channels
+    }
+}
+
+/// Identify the legacy formats without a DXGI equivalent. ddsfile can't report whether the
+/// file is palettized, so that has to come from the raw header flags. RGB layouts that
+/// [`legacy_dxgi_format`] doesn't recognise fall back to decoding by their masks
+pub fn legacy_format(pf: &PixelFormat, palettized: bool) -> Option<LegacyFormat> {
+    let bit_count = pf.rgb_bit_count.unwrap_or(0);
+    if palettized {
+        return match bit_count {
+            8 => Some(LegacyFormat::P8),
+            16 => Some(LegacyFormat::A8P8),
+            _ => None,
+        };
+    }
+    if pf.flags.contains(PixelFormatFlags::RGB) && legacy_dxgi_format(pf).is_none() {
+        return BitMasks::from_pixel_format(pf).map(LegacyFormat::Masked);
+    }
+    if !pf.flags.contains(PixelFormatFlags::LUMINANCE) {
+        return None;
+    }
+
+    // ddsfile drops the luminance mask, so the alpha mask tells them apart
+    let alpha_mask = pf
+        .a_bit_mask
+        .filter(|_| pf.flags.contains(PixelFormatFlags::ALPHA_PIXELS));
+    match (bit_count, alpha_mask) {
+        (8, None) => Some(LegacyFormat::L8),
+        (8, Some(0xf0)) => Some(LegacyFormat::A4L4),
+        (16, None) => Some(LegacyFormat::L16),
+        (16, Some(0xff00)) => Some(LegacyFormat::A8L8),
+        _ => None,
+    }
+}
+
+/// Map a legacy (pre-DX10) pixel format onto the equivalent DXGI format,
+/// so that both kinds of header go through the same decoders
+pub fn legacy_dxgi_format(pf: &PixelFormat) -> Option<DxgiFormat> {
+    if let Some(FourCC(fourcc)) = pf.fourcc {
+        return fourcc_format(fourcc);
+    }
+
+    // Masks the header doesn't set are left as None by ddsfile
+    let bit_count = pf.rgb_bit_count.unwrap_or(0);
+    let masks =
+        [pf.r_bit_mask, pf.g_bit_mask, pf.b_bit_mask, pf.a_bit_mask].map(|m| m.unwrap_or(0));
+
+    if pf.flags.contains(PixelFormatFlags::RGB) {
+        mask_format(bit_count, masks)
+    } else if pf.flags.contains(PixelFormatFlags::ALPHA) && masks[3] == 0xff {
+        Some(DxgiFormat::A8_UNorm)
+    } else {
+        None
+    }
+}
+
+fn fourcc_format(fourcc: u32) -> Option<DxgiFormat> {
+    let format = match fourcc {
+        // Premultiplied DXT2 and DXT4 share their block layout with DXT3 and DXT5
+        FourCC::DXT1 => DxgiFormat::BC1_UNorm,
+        FourCC::DXT2 | FourCC::DXT3 => DxgiFormat::BC2_UNorm,
+        FourCC::DXT4 | FourCC::DXT5 => DxgiFormat::BC3_UNorm,
+        FourCC::ATI1 | FourCC::BC4_UNORM => DxgiFormat::BC4_UNorm,
+        FourCC::BC4_SNORM => DxgiFormat::BC4_SNorm,
+        FourCC::ATI2 | BC5_UNORM_ALT => DxgiFormat::BC5_UNorm,
+        FourCC::BC5_SNORM => DxgiFormat::BC5_SNorm,
+        // D3DFORMAT enum values stored directly in the FourCC field
+        FourCC::A16B16G16R16 => DxgiFormat::R16G16B16A16_UNorm,
+        FourCC::Q16W16V16U16 => DxgiFormat::R16G16B16A16_SNorm,
+        FourCC::R16F => DxgiFormat::R16_Float,
+        FourCC::G16R16F => DxgiFormat::R16G16_Float,
+        FourCC::A16B16G16R16F => DxgiFormat::R16G16B16A16_Float,
+        FourCC::R32F => DxgiFormat::R32_Float,
+        FourCC::G32R32F => DxgiFormat::R32G32_Float,
+        FourCC::A32B32G32R32F => DxgiFormat::R32G32B32A32_Float,
+        _ => return None,
+    };
+
+    Some(format)
+}
+
+/// Uncompressed formats, identified by their bit count and R, G, B, A masks
+fn mask_format(bit_count: u32, masks: [u32; 4]) -> Option<DxgiFormat> {
+    let format = match (bit_count, masks) {
+        (32, [0xff, 0xff00, 0xff0000, 0xff000000]) => DxgiFormat::R8G8B8A8_UNorm,
+        (32, [0xff0000, 0xff00, 0xff, 0xff000000]) => DxgiFormat::B8G8R8A8_UNorm,
+        (32, [0xff0000, 0xff00, 0xff, 0]) => DxgiFormat::B8G8R8X8_UNorm,
+        (32, [0x3ff, 0xffc00, 0x3ff00000, 0xc0000000]) => DxgiFormat::R10G10B10A2_UNorm,
+        (32, [0xffff, 0xffff0000, 0, 0]) => DxgiFormat::R16G16_UNorm,
+        (16, [0xf800, 0x7e0, 0x1f, 0]) => DxgiFormat::B5G6R5_UNorm,
+        (16, [0x7c00, 0x3e0, 0x1f, 0x8000]) => DxgiFormat::B5G5R5A1_UNorm,
+        (16, [0xf00, 0xf0, 0xf, 0xf000]) => DxgiFormat::B4G4R4A4_UNorm,
+        // X1R5G5B5 and X4R4G4B4 have no DXGI equivalent. They decode as their alpha
+        // counterparts, with the padding bits ignored as the header has no alpha
+        (16, [0x7c00, 0x3e0, 0x1f, 0]) => DxgiFormat::B5G5R5A1_UNorm,
+        (16, [0xf00, 0xf0, 0xf, 0]) => DxgiFormat::B4G4R4A4_UNorm,
+        _ => return None,
+    };
+
+    Some(format)
+}
+
+#[cfg(test)]
+mod tests {
+    use ddsfile::{DxgiFormat, FourCC, PixelFormat, PixelFormatFlags};
+
+    use super::{legacy_dxgi_format, legacy_format, BitMasks, LegacyFormat};
+    use crate::pixel::RGBA;
+
+    fn fourcc(code: u32) -> PixelFormat {
+        PixelFormat {
+            flags: PixelFormatFlags::FOURCC,
+            fourcc: Some(FourCC(code)),
+            ..Default::default()
+        }
+    }
+
+    fn masks(flags: PixelFormatFlags, bit_count: u32, masks: [u32; 4]) -> PixelFormat {
+        let [r, g, b, a] = masks.map(|m| (m != 0).then_some(m));
+        PixelFormat {
+            flags,
+            rgb_bit_count: Some(bit_count),
+            r_bit_mask: r,
+            g_bit_mask: g,
+            b_bit_mask: b,
+            a_bit_mask: a,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fourcc_formats() {
+        let cases = [
+            (FourCC::DXT1, Some(DxgiFormat::BC1_UNorm)),
+            (FourCC::DXT3, Some(DxgiFormat::BC2_UNorm)),
+            (FourCC::DXT4, Some(DxgiFormat::BC3_UNorm)),
+            (FourCC::ATI1, Some(DxgiFormat::BC4_UNorm)),
+            (FourCC::BC4_SNORM, Some(DxgiFormat::BC4_SNorm)),
+            (0x55354342, Some(DxgiFormat::BC5_UNorm)),
+            (FourCC::A16B16G16R16F, Some(DxgiFormat::R16G16B16A16_Float)),
+            (FourCC::G32R32F, Some(DxgiFormat::R32G32_Float)),
+            // Packed 4:2:2 formats aren't decoded
+            (FourCC::R8G8_B8G8, None),
+            (FourCC::UYVY, None),
+        ];
+        cases.into_iter().for_each(|(code, format)| {
+            assert_eq!(legacy_dxgi_format(&fourcc(code)), format, "{code:#x}");
+        });
+    }
+
+    #[test]
+    fn test_mask_formats() {
+        let rgb = PixelFormatFlags::RGB;
+        let rgba = PixelFormatFlags::RGB | PixelFormatFlags::ALPHA_PIXELS;
+        let cases = [
+            (
+                masks(rgba, 32, [0xff0000, 0xff00, 0xff, 0xff000000]),
+                Some(DxgiFormat::B8G8R8A8_UNorm),
+            ),
+            (
+                masks(rgba, 32, [0xff, 0xff00, 0xff0000, 0xff000000]),
+                Some(DxgiFormat::R8G8B8A8_UNorm),
+            ),
+            (
+                masks(rgb, 32, [0xff0000, 0xff00, 0xff, 0]),
+                Some(DxgiFormat::B8G8R8X8_UNorm),
+            ),
+            (
+                masks(rgb, 16, [0xf800, 0x7e0, 0x1f, 0]),
+                Some(DxgiFormat::B5G6R5_UNorm),
+            ),
+            (
+                masks(rgb, 16, [0xf00, 0xf0, 0xf, 0]),
+                Some(DxgiFormat::B4G4R4A4_UNorm),
+            ),
+            (
+                masks(PixelFormatFlags::ALPHA, 8, [0, 0, 0, 0xff]),
+                Some(DxgiFormat::A8_UNorm),
+            ),
+            // A8R3G3B2 has no DXGI equivalent
+            (masks(rgba, 16, [0xe0, 0x1c, 0x3, 0xff00]), None),
+        ];
+        cases.into_iter().for_each(|(pf, format)| {
+            assert_eq!(legacy_dxgi_format(&pf), format, "{pf:?}");
+        });
+    }
+
+    #[test]
+    fn test_luminance_formats() {
+        let lum = PixelFormatFlags::LUMINANCE;
+        let lum_alpha = PixelFormatFlags::LUMINANCE | PixelFormatFlags::ALPHA_PIXELS;
+        let cases = [
+            (masks(lum, 8, [0xff, 0, 0, 0]), Some(LegacyFormat::L8)),
+            (masks(lum, 16, [0xffff, 0, 0, 0]), Some(LegacyFormat::L16)),
+            (
+                masks(lum_alpha, 16, [0xff, 0, 0, 0xff00]),
+                Some(LegacyFormat::A8L8),
+            ),
+            (
+                masks(lum_alpha, 8, [0xf, 0, 0, 0xf0]),
+                Some(LegacyFormat::A4L4),
+            ),
+        ];
+        cases.into_iter().for_each(|(pf, format)| {
+            assert_eq!(legacy_format(&pf, false), format, "{pf:?}");
+            assert_eq!(legacy_dxgi_format(&pf), None, "{pf:?}");
+        });
+
+        let pf = masks(PixelFormatFlags::empty(), 8, [0; 4]);
+        assert_eq!(legacy_format(&pf, true), Some(LegacyFormat::P8));
+        let pf = masks(PixelFormatFlags::ALPHA_PIXELS, 16, [0, 0, 0, 0xff00]);
+        assert_eq!(legacy_format(&pf, true), Some(LegacyFormat::A8P8));
+    }
+
+    #[test]
+    fn test_decode_legacy_pixel() {
+        let palette = [RGBA {
+            r: 10,
+            g: 20,
+            b: 30,
+            a: 40,
+        }; 2];
+        let rgba = |p: RGBA| [p.r, p.g, p.b, p.a];
+        let decode = |format: LegacyFormat, p: &[u8]| rgba(format.decode_pixel(p, &palette));
+
+        assert_eq!(decode(LegacyFormat::L8, &[7]), [7, 7, 7, 255]);
+        assert_eq!(
+            decode(LegacyFormat::L16, &[0x34, 0x12]),
+            [0x12, 0x12, 0x12, 255]
+        );
+        assert_eq!(decode(LegacyFormat::A8L8, &[7, 9]), [7, 7, 7, 9]);
+        assert_eq!(decode(LegacyFormat::A4L4, &[0x3f]), [255, 255, 255, 51]);
+        assert_eq!(decode(LegacyFormat::P8, &[1]), [10, 20, 30, 40]);
+        assert_eq!(decode(LegacyFormat::A8P8, &[1, 99]), [10, 20, 30, 99]);
+    }
+
+    #[test]
+    fn test_bit_masks() {
+        let rgb = PixelFormatFlags::RGB;
+        let rgba = PixelFormatFlags::RGB | PixelFormatFlags::ALPHA_PIXELS;
+
+        // R3G3B2, with alpha filled in
+        let r3g3b2 = BitMasks {
+            bit_count: 8,
+            masks: [0xe0, 0x1c, 0x3, 0],
+        };
+        assert_eq!(
+            legacy_format(&masks(rgb, 8, r3g3b2.masks), false),
+            Some(LegacyFormat::Masked(r3g3b2))
+        );
+        assert_eq!(r3g3b2.decode_channels(&[0b11101001], 8), [255, 73, 85, 255]);
+
+        // A2R10G10B10, whose masks are swapped relative to R10G10B10A2_UNORM
+        let a2r10g10b10 = BitMasks {
+            bit_count: 32,
+            masks: [0x3ff00000, 0xffc00, 0x3ff, 0xc0000000],
+        };
+        assert_eq!(
+            BitMasks::from_pixel_format(&masks(rgba, 32, a2r10g10b10.masks)),
+            Some(a2r10g10b10)
+        );
+        assert_eq!(a2r10g10b10.max_channel_bits(), 10);
+        let pixel = (0x3ffu32 << 20) | (1 << 30);
+        assert_eq!(
+            a2r10g10b10.decode_channels(&pixel.to_le_bytes(), 16),
+            [0xffff, 0, 0, 0x5555]
+        );
+
+        // 24-bit B8G8R8, where the alpha mask is ignored without ALPHA_PIXELS
+        let b8g8r8 = masks(rgb, 24, [0xff0000, 0xff00, 0xff, 0]);
+        let b8g8r8 = BitMasks::from_pixel_format(&b8g8r8).unwrap();
+        assert_eq!(b8g8r8.pixel_size(), 3);
+        assert_eq!(b8g8r8.decode_channels(&[1, 2, 3], 8), [3, 2, 1, 255]);
+
+        // Masks have to fit in the pixel, which has to be whole bytes
+        assert_eq!(
+            BitMasks::from_pixel_format(&masks(rgb, 16, [0xff0000, 0, 0, 0])),
+            None
+        );
+        assert_eq!(
+            BitMasks::from_pixel_format(&masks(rgb, 12, [0xf00, 0xf0, 0xf, 0])),
+            None
+        );
+    }
+}
@@ -1,129 +1,122 @@
 #![feature(iter_array_chunks)]
-pub mod bc7_unorm;
-
-#[cfg(test)]
-mod tests {
-    use std::{collections::HashMap, fs::File, io::BufReader, path::Path};
 
-    use bitvec::{
-        order::{Lsb0, Msb0},
-        view::BitView,
-    };
-    use ddsfile::{Caps, DataFormat, Dds, FourCC};
+/// Trace-level diagnostics, compiled out entirely unless the `trace` feature is enabled
+#[cfg(feature = "trace")]
+macro_rules! trace {
+    ($($arg:tt)*) => { log::trace!($($arg)*) };
+}
 
-    use crate::bc7_unorm::{
-        decode_block_mode_0, decode_block_mode_1, decode_block_mode_2, decode_block_mode_3,
-        decode_block_mode_4, RGB,
-    };
+#[cfg(not(feature = "trace"))]
+macro_rules! trace {
+    ($($arg:tt)*) => {};
+}
 
-    #[test]
-    fn test_load_art() {
-        let path = "/home/adam/poe_tools_data/extract/art/textures/pet/chicken/chickenns.dds";
-        let file = File::open(Path::new(path)).unwrap();
-        let mut reader = BufReader::new(file);
+pub mod bc1_unorm;
+pub mod bc2_unorm;
+pub mod bc3_unorm;
+pub mod bc4;
+pub mod bc5;
+pub mod bc6h;
+pub mod bc7_unorm;
+mod bptc;
+pub mod cubemap;
+pub mod error;
+pub mod hdr;
+pub mod legacy;
+pub mod pixel;
+#[cfg(test)]
+mod test_util;
+pub mod texture;
+pub mod uncompressed;
+pub mod visualize;
+
+use pixel::RGBA;
+pub use texture::DdsTexture;
+
+/// Combine decoded 4x4 blocks, stored in row-major order, into a single RGBA image.
+/// Pixels of a block that fall outside the image are dropped
+pub fn blocks_to_rgba_image(blocks: &[[RGBA; 16]], width: u32, height: u32) -> image::RgbaImage {
+    let blocks = blocks
+        .iter()
+        .map(|block| block.map(image::Rgba::from))
+        .collect::<Vec<_>>();
+    blocks_to_image(&blocks, width, height)
+}
 
-        let dds = Dds::read(&mut reader).unwrap();
-        println!("{:?}", dds.header);
+/// Combine decoded 4x4 blocks of any pixel type, stored in row-major order, into a single image.
+/// Pixels of a block that fall outside the image are dropped
+pub fn blocks_to_image<P: image::Pixel>(
+    blocks: &[[P; 16]],
+    width: u32,
+    height: u32,
+) -> image::ImageBuffer<P, Vec<P::Subpixel>> {
+    let num_blocks_x = width.div_ceil(4) as usize;
+
+    let mut img = image::ImageBuffer::new(width, height);
+    blocks.iter().enumerate().for_each(|(i, block)| {
+        let block_x = i % num_blocks_x;
+        let block_y = i / num_blocks_x;
+
+        block.iter().enumerate().for_each(|(j, pixel)| {
+            let x = (block_x * 4 + j % 4) as u32;
+            let y = (block_y * 4 + j / 4) as u32;
+            if x < width && y < height {
+                img.put_pixel(x, y, *pixel);
+            }
+        });
+    });
+
+    img
+}
 
-        assert_eq!(
-            dds.header.spf.fourcc,
-            Some(FourCC(FourCC::DX10)),
-            "Only DX10 files supported"
-        );
+/// Tile equally sized images left to right, then top to bottom, into a grid the given
+/// number of images wide. Unused cells in the last row are left transparent
+pub fn tile_images(images: &[image::RgbaImage], columns: u32) -> image::RgbaImage {
+    let Some(first) = images.first() else {
+        return image::RgbaImage::new(0, 0);
+    };
+    let (width, height) = first.dimensions();
+    let columns = columns.clamp(1, images.len() as u32);
+    let rows = (images.len() as u32).div_ceil(columns);
+
+    let mut img = image::RgbaImage::new(columns * width, rows * height);
+    images.iter().enumerate().for_each(|(i, tile)| {
+        let x = i as u32 % columns * width;
+        let y = i as u32 / columns * height;
+        image::imageops::replace(&mut img, tile, x as i64, y as i64);
+    });
+
+    img
+}
 
-        assert_eq!(
-            dds.header.caps & Caps::MIPMAP,
-            Caps::MIPMAP,
-            "Only mipmap files supported"
-        );
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::BufReader};
 
-        println!("{:?}, {:?}", dds.get_d3d_format(), dds.get_dxgi_format());
-        let image_height = dds.get_height() as usize;
-        let image_width = dds.get_width() as usize;
-        println!(
-            "{} {} {} {:?}",
-            dds.get_height(),
-            dds.get_width(),
-            dds.get_depth(),
-            dds.get_bits_per_pixel()
-        );
-        println!("{:?}", dds.get_num_mipmap_levels());
+    use crate::{texture::ArrayLayout, DdsTexture};
 
-        let fm = dds.get_dxgi_format().unwrap();
-        let block_size = fm.get_block_size().unwrap() as usize;
-        let block_height = fm.get_pitch_height() as usize;
+    /// Decodes a real texture from the `DDS_TEST_FILE` path into the temp directory
+    #[test]
+    #[ignore = "requires a local texture, set DDS_TEST_FILE to its path"]
+    fn test_load_art() {
+        let path = std::env::var_os("DDS_TEST_FILE").expect("DDS_TEST_FILE is not set");
+        let reader = BufReader::new(File::open(path).unwrap());
+        let texture = DdsTexture::read(reader).unwrap();
         println!(
-            "{:?}, {:?} {:?}",
-            fm.get_block_size(),
-            fm.requires_extension(),
-            fm.get_pitch_height()
+            "{}x{} {:?}, {} mips, {} layers",
+            texture.width(),
+            texture.height(),
+            texture.format(),
+            texture.mip_count(),
+            texture.array_layers()
         );
 
-        for i in 0..dds.get_num_array_layers() {
-            let data = dds.get_data(i).unwrap();
-            println!("{} {}", i, data.len());
-
-            let blocks = data
-                .chunks_exact(block_size)
-                .map(|block| block.view_bits::<Lsb0>())
-                .collect::<Vec<_>>();
-            println!("num blocks: {}", blocks.len());
-
-            let modes = blocks.iter().map(|b| b.first_one().unwrap()).fold(
-                HashMap::new(),
-                |mut acc, mode| {
-                    acc.entry(mode)
-                        .and_modify(|count: &mut usize| *count += 1)
-                        .or_default();
-
-                    acc
-                },
-            );
-            println!("modes: {:?}", modes);
-
-            //decode_block_mode_4(blocks[0]);
-            //decode_block_mode_2(blocks[60]);
-            //panic!();
-
-            let decoded = blocks
-                .into_iter()
-                .filter_map(|b| {
-                    let decoded = match b.first_one().unwrap() {
-                        0 => decode_block_mode_0(b),
-                        1 => decode_block_mode_1(b),
-                        2 => decode_block_mode_2(b),
-                        3 => decode_block_mode_3(b),
-                        4 => decode_block_mode_4(b),
-                        _ => [RGB { r: 0, g: 0, b: 0 }; 16],
-                    };
-                    Some(decoded)
-                })
-                .collect::<Vec<_>>();
-            println!("done {}", decoded.len());
-
-            // Combine blocks into single image
-            let num_blocks_y = image_height / block_height;
-            let num_blocks_x = image_width / block_height;
-            let num_blocks = num_blocks_y * num_blocks_x;
-            let biggest_image = &decoded[..num_blocks];
-
-            let mut img = image::RgbImage::new(image_width as u32, image_height as u32);
-            biggest_image.iter().enumerate().for_each(|(i, block)| {
-                let block_x = i % num_blocks_x;
-                let block_y = i / num_blocks_x;
-
-                block.iter().enumerate().for_each(|(j, pixel)| {
-                    let x_inner = j % 4;
-                    let y_inner = j / 4;
-                    let y = (block_y * block_height) + y_inner;
-                    let x = (block_x * block_height) + x_inner;
-                    //println!("{} {} {} {}", i, j, y, x);
-
-                    img.put_pixel(x as u32, y as u32, image::Rgb([pixel.r, pixel.g, pixel.b]));
-                });
-            });
-
-            img.save("test.png").unwrap();
-        }
+        texture
+            .export_layers(
+                0,
+                std::env::temp_dir().join("test.png"),
+                ArrayLayout::Separate,
+            )
+            .unwrap();
     }
 }
@@ -0,0 +1,75 @@
+/// 8-bit colour without alpha
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RGB {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+/// 8-bit colour with straight alpha, the pixel type every decoder produces
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RGBA {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl From<RGB> for RGBA {
+    /// Colour-only modes are fully opaque
+    fn from(rgb: RGB) -> Self {
+        RGBA {
+            r: rgb.r,
+            g: rgb.g,
+            b: rgb.b,
+            a: 255,
+        }
+    }
+}
+
+impl RGBA {
+    pub(crate) fn from_rgb_alpha(rgb: RGB, a: u8) -> Self {
+        RGBA { a, ..rgb.into() }
+    }
+}
+
+impl From<RGBA> for image::Rgba<u8> {
+    fn from(pixel: RGBA) -> Self {
+        image::Rgba([pixel.r, pixel.g, pixel.b, pixel.a])
+    }
+}
+
+/// Expand a `bits` wide value to `target_bits` by repeating its bit pattern, e.g. abcde becomes
+/// abcdeabc. Unlike shifting, this maps the maximum value onto the new maximum
+pub(crate) fn replicate_bits(value: u32, bits: u32, target_bits: u32) -> u32 {
+    if bits == 0 {
+        return 0;
+    }
+
+    let mut out = 0u64;
+    let mut filled = 0;
+    while filled < target_bits {
+        out = (out << bits) | value as u64;
+        filled += bits;
+    }
+
+    (out >> (filled - target_bits)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::replicate_bits;
+
+    #[test]
+    fn test_replicate_bits() {
+        assert_eq!(replicate_bits(0, 5, 8), 0);
+        assert_eq!(replicate_bits(0b11111, 5, 8), 255);
+        assert_eq!(replicate_bits(0b10000, 5, 8), 0b10000100);
+        assert_eq!(replicate_bits(0b100000, 6, 8), 0b10000010);
+        assert_eq!(replicate_bits(0b1010101, 7, 8), 0b10101011);
+        assert_eq!(replicate_bits(0xa, 4, 8), 0xaa);
+        assert_eq!(replicate_bits(1, 1, 8), 255);
+        assert_eq!(replicate_bits(0x3ff, 10, 16), 0xffff);
+        assert_eq!(replicate_bits(0x7f, 8, 8), 0x7f);
+    }
+}
@@ -0,0 +1,12 @@
+use bitvec::{order::Lsb0, vec::BitVec};
+
+/// Pack (value, width) fields LSB first into a 128 bit block
+pub(crate) fn build_block(fields: &[(u64, usize)]) -> [u8; 16] {
+    let mut bits = BitVec::<u8, Lsb0>::new();
+    fields.iter().for_each(|&(value, width)| {
+        (0..width).for_each(|i| bits.push((value >> i) & 1 == 1));
+    });
+    assert_eq!(bits.len(), 128);
+
+    bits.into_vec().try_into().unwrap()
+}
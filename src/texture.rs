@@ -0,0 +1,1547 @@
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use ddsfile::{
+    Caps2, D3D10ResourceDimension, DataFormat, Dds, DxgiFormat, MiscFlag, PixelFormatFlags,
+};
+use image::{
+    DynamicImage, GrayAlphaImage, GrayImage, ImageBuffer, Luma, LumaA, Pixel, Rgb, Rgb32FImage,
+    RgbImage, Rgba, Rgba32FImage, RgbaImage,
+};
+
+use crate::{
+    bc1_unorm::{self, decode_bc1_block},
+    bc2_unorm::{self, decode_bc2_block},
+    bc3_unorm::{self, decode_bc3_block},
+    bc4::{self, decode_bc4_snorm_block, decode_bc4_unorm_block, snorm_to_f32, snorm_to_unorm},
+    bc5::{self, decode_bc5_snorm_block, decode_bc5_unorm_block, reconstruct_z},
+    bc6h::{self, decode_bc6h_block},
+    bc7_unorm::{self, decode_bc7_block},
+    blocks_to_image, blocks_to_rgba_image,
+    cubemap::{layout_faces, CubeFace, CubeLayout},
+    error::DecodeError,
+    hdr::{tonemap_image, HdrOptions},
+    legacy::{legacy_dxgi_format, legacy_format, LegacyFormat, PALETTE_INDEXED_8, PALETTE_LEN},
+    pixel::RGBA,
+    tile_images,
+    uncompressed::{channel_layout, float_pixel_codec, pixel_codec},
+    visualize::{visualize, Visualization},
+};
+
+/// Position and block grid of one mip level within an array layer.
+/// Uncompressed formats are treated as having 1x1 blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MipLevel {
+    pub level: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Number of depth slices, 1 unless this is a volume texture
+    pub depth: u32,
+    pub blocks_x: u32,
+    pub blocks_y: u32,
+    /// Bytes from the start of one row of blocks to the next
+    pub row_pitch: usize,
+    /// Byte offset from the start of the layer's data
+    pub offset: usize,
+    /// Size in bytes, covering every depth slice
+    pub size: usize,
+}
+
+impl MipLevel {
+    /// Size in bytes of a single depth slice
+    pub fn slice_size(&self) -> usize {
+        self.size / self.depth as usize
+    }
+}
+
+/// One mip level of one array layer
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subresource {
+    pub layer: u32,
+    pub mip: MipLevel,
+}
+
+/// How the layers of an array texture are written out
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ArrayLayout {
+    /// One file per layer, with the layer index appended to the file name
+    Separate,
+    /// A single image with layers tiled left to right, then top to bottom
+    Grid { columns: u32 },
+}
+
+/// Decodes one compressed block into a row-major 4x4 grid of pixels
+type BlockDecoder = fn(&[u8]) -> Result<[RGBA; 16], DecodeError>;
+
+/// Compressed block size in bytes and decoder for a format, if it's supported.
+/// sRGB variants decode the same way, leaving the stored values as they are
+fn block_codec(format: DxgiFormat) -> Option<(usize, BlockDecoder)> {
+    match format {
+        DxgiFormat::BC1_UNorm | DxgiFormat::BC1_UNorm_sRGB => {
+            Some((bc1_unorm::BLOCK_SIZE, decode_bc1_block))
+        }
+        DxgiFormat::BC2_UNorm | DxgiFormat::BC2_UNorm_sRGB => {
+            Some((bc2_unorm::BLOCK_SIZE, decode_bc2_block))
+        }
+        DxgiFormat::BC3_UNorm | DxgiFormat::BC3_UNorm_sRGB => {
+            Some((bc3_unorm::BLOCK_SIZE, decode_bc3_block))
+        }
+        DxgiFormat::BC7_UNorm | DxgiFormat::BC7_UNorm_sRGB => {
+            Some((bc7_unorm::BLOCK_SIZE, decode_bc7_block))
+        }
+        // Single channel formats come out grey, two channel formats as red and green.
+        // Signed values are remapped so that -1.0 is 0 and 1.0 is 255
+        DxgiFormat::BC4_UNorm => Some((bc4::BLOCK_SIZE, |block| {
+            Ok(decode_bc4_unorm_block(block)?.map(grey))
+        })),
+        DxgiFormat::BC4_SNorm => Some((bc4::BLOCK_SIZE, |block| {
+            Ok(decode_bc4_snorm_block(block)?.map(|v| grey(snorm_to_unorm(v))))
+        })),
+        DxgiFormat::BC5_UNorm => Some((bc5::BLOCK_SIZE, |block| {
+            Ok(decode_bc5_unorm_block(block)?.map(red_green))
+        })),
+        DxgiFormat::BC5_SNorm => Some((bc5::BLOCK_SIZE, |block| {
+            Ok(decode_bc5_snorm_block(block)?.map(|rg| red_green(rg.map(snorm_to_unorm))))
+        })),
+        // HDR formats are tonemapped with the default options, see DdsTexture::decode_hdr
+        // for the linear values
+        DxgiFormat::BC6H_UF16 => Some((bc6h::BLOCK_SIZE, |block| {
+            Ok(decode_bc6h_block(block, false)?.map(tonemapped))
+        })),
+        DxgiFormat::BC6H_SF16 => Some((bc6h::BLOCK_SIZE, |block| {
+            Ok(decode_bc6h_block(block, true)?.map(tonemapped))
+        })),
+        _ => None,
+    }
+}
+
+fn tonemapped(rgb: [f32; 3]) -> RGBA {
+    let [r, g, b] = HdrOptions::default().apply(rgb);
+    RGBA { r, g, b, a: 255 }
+}
+
+/// Decode every pixel of an uncompressed surface, whose rows are `row_pitch` bytes apart
+fn decode_pixels<P: Pixel>(
+    mip: &MipLevel,
+    data: &[u8],
+    pixel_size: usize,
+    decode_pixel: impl Fn(&[u8]) -> P,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    ImageBuffer::from_fn(mip.width, mip.height, |x, y| {
+        let start = y as usize * mip.row_pitch + x as usize * pixel_size;
+        decode_pixel(&data[start..start + pixel_size])
+    })
+}
+
+fn grey(value: u8) -> RGBA {
+    RGBA {
+        r: value,
+        g: value,
+        b: value,
+        a: 255,
+    }
+}
+
+fn red_green([r, g]: [u8; 2]) -> RGBA {
+    RGBA { r, g, b: 0, a: 255 }
+}
+
+/// A DDS file, decodable into images one surface at a time
+pub struct DdsTexture {
+    dds: Dds,
+    /// Colours of a palettized legacy file, which ddsfile leaves at the start of the data
+    palette: Option<Vec<RGBA>>,
+}
+
+/// Byte offsets of the pixel format's flags and bit count, counting the magic number
+const PIXEL_FORMAT_FLAGS_OFFSET: usize = 80;
+const PIXEL_FORMAT_BIT_COUNT_OFFSET: usize = 88;
+
+impl DdsTexture {
+    /// Wrap an already parsed file. Palettized files can't be recognised from a [`Dds`] alone,
+    /// use [`Self::read`] for those
+    pub fn new(dds: Dds) -> Self {
+        Self { dds, palette: None }
+    }
+
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, DecodeError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(ddsfile::Error::Io)?;
+        let mut dds = Dds::read(bytes.as_slice())?;
+
+        // ddsfile drops both the palettized flag and the bit count that goes with it, so they're
+        // read from the raw header. A successful read means the header is all there
+        let raw = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        if dds.header10.is_some() || raw(PIXEL_FORMAT_FLAGS_OFFSET) & PALETTE_INDEXED_8 == 0 {
+            return Ok(Self::new(dds));
+        }
+        dds.header.spf.rgb_bit_count = Some(raw(PIXEL_FORMAT_BIT_COUNT_OFFSET));
+
+        let palette_size = PALETTE_LEN * 4;
+        if dds.data.len() < palette_size {
+            return Err(DecodeError::TruncatedData {
+                expected: palette_size,
+                actual: dds.data.len(),
+            });
+        }
+        let palette = dds
+            .data
+            .drain(..palette_size)
+            .array_chunks()
+            .map(|[r, g, b, a]| RGBA { r, g, b, a })
+            .collect();
+
+        Ok(Self {
+            dds,
+            palette: Some(palette),
+        })
+    }
+
+    /// The underlying parsed file
+    pub fn dds(&self) -> &Dds {
+        &self.dds
+    }
+
+    pub fn width(&self) -> u32 {
+        self.dds.get_width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.dds.get_height()
+    }
+
+    /// Number of depth slices at the top mip level, 1 unless this is a volume texture
+    pub fn depth(&self) -> u32 {
+        self.dds.get_depth().max(1)
+    }
+
+    /// Pixel format, with legacy FourCC and bitmask headers mapped onto their DXGI equivalent
+    pub fn format(&self) -> Option<DxgiFormat> {
+        match &self.dds.header10 {
+            Some(h10) => Some(h10.dxgi_format),
+            None => legacy_dxgi_format(&self.dds.header.spf),
+        }
+    }
+
+    /// Format of a legacy file with no DXGI equivalent, such as luminance or palettized ones
+    pub fn legacy_format(&self) -> Option<LegacyFormat> {
+        if self.dds.header10.is_some() {
+            return None;
+        }
+        legacy_format(&self.dds.header.spf, self.palette.is_some())
+    }
+
+    /// Colours of a palettized file, indexed by the pixel data
+    pub fn palette(&self) -> Option<&[RGBA]> {
+        self.palette.as_deref()
+    }
+
+    /// Number of mip levels, counting a header that claims 0 as 1 like DirectXTex does
+    pub fn mip_count(&self) -> u32 {
+        self.dds.get_num_mipmap_levels().max(1)
+    }
+
+    pub fn is_cubemap(&self) -> bool {
+        match &self.dds.header10 {
+            Some(h10) => h10.misc_flag.contains(MiscFlag::TEXTURECUBE),
+            None => self.dds.header.caps2.contains(Caps2::CUBEMAP),
+        }
+    }
+
+    pub fn is_volume(&self) -> bool {
+        match &self.dds.header10 {
+            Some(h10) => h10.resource_dimension == D3D10ResourceDimension::Texture3D,
+            None => self.dds.header.caps2.contains(Caps2::VOLUME),
+        }
+    }
+
+    /// Number of 2D surfaces, each with its own mip chain.
+    /// Every face of a cube map counts as a layer, so cube n starts at layer 6 * n
+    pub fn array_layers(&self) -> u32 {
+        match &self.dds.header10 {
+            // DX10 headers count whole cubes rather than faces
+            Some(h10) if self.is_cubemap() => h10.array_size * 6,
+            _ => self.dds.get_num_array_layers(),
+        }
+    }
+
+    /// Number of cubes in a cube map array, 0 if this isn't a cube map
+    pub fn cube_count(&self) -> u32 {
+        if self.is_cubemap() {
+            self.array_layers() / 6
+        } else {
+            0
+        }
+    }
+
+    /// Block edge length in pixels and size in bytes.
+    /// Unknown formats are assumed to use 16 byte 4x4 blocks
+    fn block_layout(&self) -> (u32, usize) {
+        let Some(format) = self.format() else {
+            return match self.legacy_format() {
+                Some(legacy) => (1, legacy.pixel_size()),
+                None => (4, 16),
+            };
+        };
+        match (format.get_block_size(), format.get_bits_per_pixel()) {
+            (Some(size), _) => (4, size as usize),
+            (None, Some(bits)) => (1, (bits as usize).div_ceil(8)),
+            (None, None) => (4, 16),
+        }
+    }
+
+    /// Alignment in bytes of every row of uncompressed pixels. Writers may pad rows to at most
+    /// a DWORD, which only the header's pitch for the top level reflects. Some writers store a
+    /// pitch that isn't a row pitch at all, e.g. the surface size, which is ignored as long as
+    /// the data holds the unpadded layout
+    fn row_alignment(&self) -> Result<usize, DecodeError> {
+        let (block_dim, block_size) = self.block_layout();
+        let row_size = self.width().div_ceil(block_dim) as usize * block_size;
+        let pitch = match self.dds.header.pitch {
+            Some(pitch) if block_dim == 1 && pitch as usize > row_size => pitch,
+            _ => return Ok(1),
+        };
+
+        if let Some(alignment) = [1, 2, 4]
+            .into_iter()
+            .find(|&alignment| row_size.next_multiple_of(alignment) == pitch as usize)
+        {
+            return Ok(alignment);
+        }
+
+        let layer_size = self
+            .aligned_mip_levels(1)
+            .iter()
+            .map(|m| m.size)
+            .sum::<usize>();
+        if self.dds.data.len() >= layer_size * self.array_layers() as usize {
+            Ok(1)
+        } else {
+            Err(DecodeError::UnsupportedPitch { pitch, row_size })
+        }
+    }
+
+    /// Layout of every mip level in a layer, from largest to smallest.
+    /// Rows are assumed to be unpadded if the header's pitch can't be explained by an alignment,
+    /// which decoding reports as [`DecodeError::UnsupportedPitch`]
+    pub fn mip_levels(&self) -> Vec<MipLevel> {
+        self.aligned_mip_levels(self.row_alignment().unwrap_or(1))
+    }
+
+    fn aligned_mip_levels(&self, alignment: usize) -> Vec<MipLevel> {
+        let (block_dim, block_size) = self.block_layout();
+        let mut offset = 0;
+        (0..self.mip_count())
+            .map(|level| {
+                let width = (self.width() >> level).max(1);
+                let height = (self.height() >> level).max(1);
+                let depth = (self.depth() >> level).max(1);
+                // Partial blocks on the right and bottom edges are still stored whole
+                let blocks_x = width.div_ceil(block_dim);
+                let blocks_y = height.div_ceil(block_dim);
+
+                let row_pitch = (blocks_x as usize * block_size).next_multiple_of(alignment);
+                // Volume textures store a full grid of blocks for each slice
+                let size = row_pitch * (blocks_y * depth) as usize;
+
+                let mip = MipLevel {
+                    level,
+                    width,
+                    height,
+                    depth,
+                    blocks_x,
+                    blocks_y,
+                    row_pitch,
+                    offset,
+                    size,
+                };
+                offset += size;
+                mip
+            })
+            .collect()
+    }
+
+    /// Layout of a single mip level, if it exists
+    pub fn mip_level(&self, level: u32) -> Option<MipLevel> {
+        self.mip_levels().get(level as usize).copied()
+    }
+
+    /// Every (layer, mip) pair in storage order: each layer's full mip chain, one layer after another
+    pub fn subresources(&self) -> impl Iterator<Item = Subresource> {
+        let mips = self.mip_levels();
+        (0..self.array_layers()).flat_map(move |layer| {
+            mips.clone()
+                .into_iter()
+                .map(move |mip| Subresource { layer, mip })
+        })
+    }
+
+    /// Decode every subresource in storage order, see [`Self::subresources`]
+    pub fn decode_subresources(
+        &self,
+    ) -> impl Iterator<Item = (Subresource, Result<RgbaImage, DecodeError>)> + '_ {
+        self.subresources()
+            .map(|sub| (sub, self.decode_mip(sub.layer, sub.mip.level)))
+    }
+
+    /// Decode a single mip level of a single array layer.
+    /// For volume textures this is the first depth slice, see [`Self::decode_slice`]
+    pub fn decode_mip(&self, layer: u32, level: u32) -> Result<RgbaImage, DecodeError> {
+        self.decode_surface(layer, level, 0)
+    }
+
+    /// Layout and raw bytes of one depth slice of one mip level of one array layer
+    fn surface_data(
+        &self,
+        layer: u32,
+        level: u32,
+        slice: u32,
+    ) -> Result<(MipLevel, &[u8]), DecodeError> {
+        if layer >= self.array_layers() {
+            return Err(DecodeError::OutOfBounds { layer, level });
+        }
+        self.row_alignment()?;
+        let mips = self.mip_levels();
+        let mip = mips
+            .get(level as usize)
+            .ok_or(DecodeError::OutOfBounds { layer, level })?;
+        if slice >= mip.depth {
+            return Err(DecodeError::SliceOutOfBounds {
+                slice,
+                depth: mip.depth,
+            });
+        }
+
+        // Layers are stored one after another, each with its full mip chain
+        let layer_size = mips.iter().map(|m| m.size).sum::<usize>();
+        let slice_size = mip.slice_size();
+        let offset = layer as usize * layer_size + mip.offset + slice as usize * slice_size;
+        let data =
+            self.dds
+                .data
+                .get(offset..offset + slice_size)
+                .ok_or(DecodeError::TruncatedData {
+                    expected: offset + slice_size,
+                    actual: self.dds.data.len(),
+                })?;
+
+        Ok((*mip, data))
+    }
+
+    fn decode_surface(&self, layer: u32, level: u32, slice: u32) -> Result<RgbaImage, DecodeError> {
+        if let Some(legacy) = self.legacy_format() {
+            let palette = self.palette.as_deref().unwrap_or_default();
+            let (mip, data) = self.surface_data(layer, level, slice)?;
+            return Ok(decode_pixels(&mip, data, legacy.pixel_size(), |p| {
+                legacy.decode_pixel(p, palette).into()
+            }));
+        }
+
+        let format = self.format();
+        if let Some((pixel_size, decode_pixel)) = format.and_then(pixel_codec) {
+            // Legacy headers without an alpha flag leave any alpha bits as padding
+            let flags = self.dds.header.spf.flags;
+            let opaque = self.dds.header10.is_none()
+                && flags.contains(PixelFormatFlags::RGB)
+                && !flags.contains(PixelFormatFlags::ALPHA_PIXELS);
+
+            let (mip, data) = self.surface_data(layer, level, slice)?;
+            return Ok(decode_pixels(&mip, data, pixel_size, |p| {
+                let mut pixel = decode_pixel(p);
+                if opaque {
+                    pixel.a = 255;
+                }
+                pixel.into()
+            }));
+        }
+
+        // Float formats are tonemapped like BC6H, with alpha clamped to [0, 1]
+        if let Some((pixel_size, decode_pixel)) = format.and_then(float_pixel_codec) {
+            let (mip, data) = self.surface_data(layer, level, slice)?;
+            return Ok(decode_pixels(&mip, data, pixel_size, |p| {
+                let [r, g, b, a] = decode_pixel(p);
+                let mut pixel = tonemapped([r, g, b]);
+                pixel.a = (a.clamp(0.0, 1.0) * 255.0).round() as u8;
+                pixel.into()
+            }));
+        }
+
+        if format.and_then(channel_layout).is_some() {
+            return self.visualize_surface(layer, level, slice, Visualization::default());
+        }
+
+        let (block_size, decode_block) = format
+            .and_then(block_codec)
+            .ok_or(DecodeError::UnsupportedFormat(format))?;
+        let (mip, data) = self.surface_data(layer, level, slice)?;
+
+        let blocks = data
+            .chunks_exact(block_size)
+            .map(decode_block)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(blocks_to_rgba_image(&blocks, mip.width, mip.height))
+    }
+
+    /// Decode a single mip level of a single array layer, keeping only the channels the
+    /// format stores: single channel formats as greyscale, BC5 as red and green with blue left
+    /// at 0, 10-bit formats as 16-bit and HDR formats as linear floats.
+    /// Legacy luminance formats come out as greyscale, with alpha if they have it, and
+    /// bitmask formats with channels wider than 8 bits as 16-bit
+    pub fn decode_image(&self, layer: u32, level: u32) -> Result<DynamicImage, DecodeError> {
+        match self.legacy_format() {
+            Some(LegacyFormat::L16) => {
+                let (mip, data) = self.surface_data(layer, level, 0)?;
+                let img =
+                    decode_pixels(&mip, data, 2, |p| Luma([u16::from_le_bytes([p[0], p[1]])]));
+                return Ok(DynamicImage::ImageLuma16(img));
+            }
+            Some(LegacyFormat::L8) => {
+                let img = self.decode_mip(layer, level)?;
+                return Ok(DynamicImage::ImageLuma8(GrayImage::from_fn(
+                    img.width(),
+                    img.height(),
+                    |x, y| Luma([img.get_pixel(x, y)[0]]),
+                )));
+            }
+            // Keep the extra precision of channels wider than 8 bits
+            Some(LegacyFormat::Masked(masks)) if masks.max_channel_bits() > 8 => {
+                let (mip, data) = self.surface_data(layer, level, 0)?;
+                let img = decode_pixels(&mip, data, masks.pixel_size(), |p| {
+                    Rgba(masks.decode_channels(p, 16))
+                });
+                return Ok(DynamicImage::ImageRgba16(img));
+            }
+            Some(LegacyFormat::A8L8 | LegacyFormat::A4L4) => {
+                let img = self.decode_mip(layer, level)?;
+                return Ok(DynamicImage::ImageLumaA8(GrayAlphaImage::from_fn(
+                    img.width(),
+                    img.height(),
+                    |x, y| {
+                        let [l, _, _, a] = img.get_pixel(x, y).0;
+                        LumaA([l, a])
+                    },
+                )));
+            }
+            _ => {}
+        }
+
+        Ok(match self.format() {
+            Some(DxgiFormat::BC4_UNorm | DxgiFormat::BC4_SNorm | DxgiFormat::R8_UNorm) => {
+                let img = self.decode_mip(layer, level)?;
+                DynamicImage::ImageLuma8(GrayImage::from_fn(img.width(), img.height(), |x, y| {
+                    Luma([img.get_pixel(x, y)[0]])
+                }))
+            }
+            Some(DxgiFormat::BC5_UNorm | DxgiFormat::BC5_SNorm) => DynamicImage::ImageRgb8(
+                DynamicImage::ImageRgba8(self.decode_mip(layer, level)?).into_rgb8(),
+            ),
+            Some(
+                DxgiFormat::BC6H_UF16
+                | DxgiFormat::BC6H_SF16
+                | DxgiFormat::R11G11B10_Float
+                | DxgiFormat::R9G9B9E5_SharedExp
+                | DxgiFormat::R16_Float
+                | DxgiFormat::R16G16_Float
+                | DxgiFormat::R32_Float
+                | DxgiFormat::R32G32_Float,
+            ) => DynamicImage::ImageRgb32F(self.decode_hdr(layer, level)?),
+            Some(DxgiFormat::R16G16B16A16_Float | DxgiFormat::R32G32B32A32_Float) => {
+                DynamicImage::ImageRgba32F(self.decode_float(layer, level)?)
+            }
+            Some(DxgiFormat::R10G10B10A2_UNorm) => DynamicImage::ImageRgba16(
+                DynamicImage::ImageRgba32F(self.decode_float(layer, level)?).into_rgba16(),
+            ),
+            _ => DynamicImage::ImageRgba8(self.decode_mip(layer, level)?),
+        })
+    }
+
+    /// Decode a single mip level of a single array layer of a UNORM, SNORM, UINT or SINT format,
+    /// turning its raw values into colours as `mode` describes
+    pub fn decode_visualized(
+        &self,
+        layer: u32,
+        level: u32,
+        mode: Visualization,
+    ) -> Result<RgbaImage, DecodeError> {
+        self.visualize_surface(layer, level, 0, mode)
+    }
+
+    fn visualize_surface(
+        &self,
+        layer: u32,
+        level: u32,
+        slice: u32,
+        mode: Visualization,
+    ) -> Result<RgbaImage, DecodeError> {
+        let format = self.format();
+        let layout = format
+            .and_then(channel_layout)
+            .ok_or(DecodeError::UnsupportedFormat(format))?;
+        let (mip, data) = self.surface_data(layer, level, slice)?;
+
+        let values = data
+            .chunks(mip.row_pitch)
+            .take(mip.height as usize)
+            .flat_map(|row| {
+                row.chunks_exact(layout.pixel_size())
+                    .take(mip.width as usize)
+                    .map(|p| layout.decode_pixel(p))
+            })
+            .collect::<Vec<_>>();
+
+        Ok(visualize(&values, mip.width, mip.height, layout, mode))
+    }
+
+    /// Decode a single mip level of a single array layer of an HDR or 10-bit format into
+    /// RGBA floats. BC6H has no alpha channel, so it comes out as 1.0
+    pub fn decode_float(&self, layer: u32, level: u32) -> Result<Rgba32FImage, DecodeError> {
+        let format = self.format();
+        if let Some((pixel_size, decode_pixel)) = format.and_then(float_pixel_codec) {
+            let (mip, data) = self.surface_data(layer, level, 0)?;
+            return Ok(decode_pixels(&mip, data, pixel_size, |p| {
+                Rgba(decode_pixel(p))
+            }));
+        }
+
+        Ok(DynamicImage::ImageRgb32F(self.decode_hdr(layer, level)?).into_rgba32f())
+    }
+
+    /// Decode a single mip level of a single array layer of an HDR format into linear values,
+    /// dropping any alpha. R10G10B10A2_UNORM isn't linear HDR data, so it's left to
+    /// [`Self::decode_float`] and [`Self::decode_image`]
+    pub fn decode_hdr(&self, layer: u32, level: u32) -> Result<Rgb32FImage, DecodeError> {
+        let format = self.format();
+        if format == Some(DxgiFormat::R10G10B10A2_UNorm) {
+            return Err(DecodeError::UnsupportedFormat(format));
+        }
+        if format.and_then(float_pixel_codec).is_some() {
+            let img = self.decode_float(layer, level)?;
+            return Ok(DynamicImage::ImageRgba32F(img).into_rgb32f());
+        }
+
+        let signed = match format {
+            Some(DxgiFormat::BC6H_UF16) => false,
+            Some(DxgiFormat::BC6H_SF16) => true,
+            _ => return Err(DecodeError::UnsupportedFormat(format)),
+        };
+        let (mip, data) = self.surface_data(layer, level, 0)?;
+
+        let blocks = data
+            .chunks_exact(bc6h::BLOCK_SIZE)
+            .map(|block| Ok(decode_bc6h_block(block, signed)?.map(Rgb)))
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+
+        Ok(blocks_to_image(&blocks, mip.width, mip.height))
+    }
+
+    /// Decode an HDR format into 8-bit sRGB, with the given exposure and tonemapping
+    pub fn decode_tonemapped(
+        &self,
+        layer: u32,
+        level: u32,
+        options: HdrOptions,
+    ) -> Result<RgbImage, DecodeError> {
+        Ok(tonemap_image(&self.decode_hdr(layer, level)?, options))
+    }
+
+    /// Decode a BC5 tangent-space normal map, reconstructing Z from the stored X and Y.
+    /// Components are mapped from [-1.0, 1.0] onto [0, 255]
+    pub fn decode_normal_map(&self, layer: u32, level: u32) -> Result<RgbImage, DecodeError> {
+        let signed = match self.format() {
+            Some(DxgiFormat::BC5_UNorm) => false,
+            Some(DxgiFormat::BC5_SNorm) => true,
+            format => return Err(DecodeError::UnsupportedFormat(format)),
+        };
+        let (mip, data) = self.surface_data(layer, level, 0)?;
+
+        let to_byte = |v: f32| ((v * 0.5 + 0.5) * 255.0).round().clamp(0.0, 255.0) as u8;
+        let blocks = data
+            .chunks_exact(bc5::BLOCK_SIZE)
+            .map(|block| {
+                let xy = if signed {
+                    decode_bc5_snorm_block(block)?.map(|p| p.map(snorm_to_f32))
+                } else {
+                    decode_bc5_unorm_block(block)?.map(|p| p.map(|v| v as f32 / 127.5 - 1.0))
+                };
+
+                Ok(xy.map(|[x, y]| RGBA {
+                    r: to_byte(x),
+                    g: to_byte(y),
+                    b: to_byte(reconstruct_z(x, y)),
+                    a: 255,
+                }))
+            })
+            .collect::<Result<Vec<_>, DecodeError>>()?;
+
+        Ok(
+            DynamicImage::ImageRgba8(blocks_to_rgba_image(&blocks, mip.width, mip.height))
+                .into_rgb8(),
+        )
+    }
+
+    /// Decode one mip level of every array layer
+    pub fn decode_layers(&self, level: u32) -> Result<Vec<RgbaImage>, DecodeError> {
+        (0..self.array_layers())
+            .map(|layer| self.decode_mip(layer, level))
+            .collect()
+    }
+
+    /// Decode one mip level of every array layer and save it to disk.
+    /// With [`ArrayLayout::Separate`], `path` is used as a template: `layers.png` becomes
+    /// `layers_0.png`, `layers_1.png`, ... Cube map faces are named after the cube and face
+    /// instead, e.g. `layers_0_+x.png`. Returns the paths written
+    pub fn export_layers(
+        &self,
+        level: u32,
+        path: impl AsRef<Path>,
+        layout: ArrayLayout,
+    ) -> Result<Vec<PathBuf>, DecodeError> {
+        let path = path.as_ref();
+        let layers = self.decode_layers(level)?;
+
+        match layout {
+            ArrayLayout::Separate => layers
+                .iter()
+                .enumerate()
+                .map(|(layer, img)| {
+                    let mut name = path.file_stem().unwrap_or_default().to_owned();
+                    if self.is_cubemap() {
+                        let face = CubeFace::ALL[layer % 6];
+                        name.push(format!("_{}_{}", layer / 6, face.label()));
+                    } else {
+                        name.push(format!("_{layer}"));
+                    }
+                    let mut layer_path = path.with_file_name(name);
+                    if let Some(ext) = path.extension() {
+                        layer_path.set_extension(ext);
+                    }
+
+                    img.save(&layer_path)?;
+                    Ok(layer_path)
+                })
+                .collect(),
+            ArrayLayout::Grid { columns } => {
+                tile_images(&layers, columns).save(path)?;
+                Ok(vec![path.to_path_buf()])
+            }
+        }
+    }
+
+    /// Decode one depth slice of a volume texture at one mip level
+    pub fn decode_slice(&self, level: u32, slice: u32) -> Result<RgbaImage, DecodeError> {
+        self.decode_surface(0, level, slice)
+    }
+
+    /// Decode every depth slice of a volume texture at one mip level, front to back
+    pub fn decode_slices(&self, level: u32) -> Result<Vec<RgbaImage>, DecodeError> {
+        let depth = self
+            .mip_level(level)
+            .ok_or(DecodeError::OutOfBounds { layer: 0, level })?
+            .depth;
+        (0..depth)
+            .map(|slice| self.decode_slice(level, slice))
+            .collect()
+    }
+
+    /// Decode every depth slice of a volume texture at one mip level, tiled left to right
+    /// then top to bottom into an atlas the given number of slices wide
+    pub fn decode_volume_atlas(&self, level: u32, columns: u32) -> Result<RgbaImage, DecodeError> {
+        Ok(tile_images(&self.decode_slices(level)?, columns))
+    }
+
+    /// Decode the six faces of a cube at one mip level, in +X, -X, +Y, -Y, +Z, -Z order
+    pub fn decode_cube_faces(&self, cube: u32, level: u32) -> Result<Vec<RgbaImage>, DecodeError> {
+        if cube >= self.cube_count() {
+            return Err(DecodeError::OutOfBounds {
+                layer: cube * 6,
+                level,
+            });
+        }
+
+        (0..6)
+            .map(|face| self.decode_mip(cube * 6 + face, level))
+            .collect()
+    }
+
+    /// Decode a cube at one mip level, arranged as a cross, a strip or separate faces
+    pub fn decode_cube(
+        &self,
+        cube: u32,
+        level: u32,
+        layout: CubeLayout,
+    ) -> Result<Vec<RgbaImage>, DecodeError> {
+        Ok(layout_faces(self.decode_cube_faces(cube, level)?, layout))
+    }
+
+    /// Decode the full mip chain of a single array layer, from largest to smallest
+    pub fn decode_mips(&self, layer: u32) -> Result<Vec<RgbaImage>, DecodeError> {
+        (0..self.mip_count())
+            .map(|level| self.decode_mip(layer, level))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ddsfile::{
+        AlphaMode, Caps2, D3D10ResourceDimension, D3DFormat, Dds, DxgiFormat, FourCC, NewD3dParams,
+        NewDxgiParams, PixelFormat, PixelFormatFlags,
+    };
+
+    use super::{ArrayLayout, DdsTexture, MipLevel, Subresource};
+    use crate::{
+        cubemap::CubeLayout,
+        error::DecodeError,
+        hdr::{HdrOptions, Tonemap},
+        legacy::LegacyFormat,
+        test_util::build_block,
+        visualize::Visualization,
+    };
+
+    /// Mode 6 block where every pixel is the given opaque grey.
+    /// The p-bits are set for an opaque alpha, so value has to be odd
+    fn solid_bc7_block(value: u8) -> [u8; 16] {
+        assert_eq!(value & 1, 1);
+        // Mode bits, 7 bit R0 R1 G0 G1 B0 B1 A0 A1, p-bits, then all indices 0
+        let mut fields = vec![(1 << 6, 7)];
+        fields.extend([((value >> 1) as u64, 7); 6]);
+        fields.extend([(0x7f, 7); 2]);
+        fields.extend([(0b11, 2), (0, 63)]);
+
+        build_block(&fields)
+    }
+
+    fn new_texture(width: u32, height: u32, mipmap_levels: u32, format: DxgiFormat) -> Dds {
+        Dds::new_dxgi(texture_params(width, height, mipmap_levels, format)).unwrap()
+    }
+
+    /// Parameters for a plain 2D texture, to extend into arrays, volumes and cube maps
+    fn texture_params(
+        width: u32,
+        height: u32,
+        mipmap_levels: u32,
+        format: DxgiFormat,
+    ) -> NewDxgiParams {
+        NewDxgiParams {
+            height,
+            width,
+            depth: None,
+            format,
+            mipmap_levels: Some(mipmap_levels),
+            array_layers: None,
+            caps2: None,
+            is_cubemap: false,
+            resource_dimension: D3D10ResourceDimension::Texture2D,
+            alpha_mode: AlphaMode::Straight,
+        }
+    }
+
+    fn new_legacy_texture(format: D3DFormat, width: u32, height: u32, data: Vec<u8>) -> Dds {
+        let mut dds = Dds::new_d3d(NewD3dParams {
+            height,
+            width,
+            depth: None,
+            format,
+            mipmap_levels: None,
+            caps2: None,
+        })
+        .unwrap();
+        dds.data = data;
+        dds
+    }
+
+    #[test]
+    fn test_metadata() {
+        let texture = DdsTexture::new(new_texture(8, 4, 2, DxgiFormat::BC7_UNorm));
+        assert_eq!(texture.width(), 8);
+        assert_eq!(texture.height(), 4);
+        assert_eq!(texture.format(), Some(DxgiFormat::BC7_UNorm));
+        assert_eq!(texture.mip_count(), 2);
+        assert_eq!(texture.array_layers(), 1);
+        assert_eq!(texture.depth(), 1);
+        assert!(!texture.is_volume());
+    }
+
+    #[test]
+    fn test_legacy_metadata() {
+        let dds = new_legacy_texture(D3DFormat::DXT5, 8, 8, vec![0; 64]);
+        let texture = DdsTexture::new(dds);
+        assert_eq!(texture.format(), Some(DxgiFormat::BC3_UNorm));
+        assert_eq!(texture.array_layers(), 1);
+        assert!(!texture.is_cubemap());
+    }
+
+    #[test]
+    fn test_decode_mip() {
+        let mut dds = new_texture(16, 8, 2, DxgiFormat::BC7_UNorm);
+        // 8 blocks for the top level, 2 for the second
+        let data = (0..8)
+            .map(|_| solid_bc7_block(0x21))
+            .chain((0..2).map(|_| solid_bc7_block(0x81)))
+            .flatten()
+            .collect::<Vec<_>>();
+        dds.data[..data.len()].copy_from_slice(&data);
+        let texture = DdsTexture::new(dds);
+
+        let top = texture.decode_mip(0, 0).unwrap();
+        assert_eq!(top.dimensions(), (16, 8));
+        assert!(top.pixels().all(|p| p.0 == [0x21, 0x21, 0x21, 0xff]));
+
+        let second = texture.decode_mip(0, 1).unwrap();
+        assert_eq!(second.dimensions(), (8, 4));
+        assert!(second.pixels().all(|p| p.0 == [0x81, 0x81, 0x81, 0xff]));
+    }
+
+    #[test]
+    fn test_mip_levels() {
+        let texture = DdsTexture::new(new_texture(16, 8, 5, DxgiFormat::BC7_UNorm));
+        let mips = texture.mip_levels();
+        assert_eq!(mips.len(), 5);
+        assert_eq!(
+            mips[1],
+            MipLevel {
+                level: 1,
+                width: 8,
+                height: 4,
+                depth: 1,
+                blocks_x: 2,
+                blocks_y: 1,
+                row_pitch: 2 * 16,
+                offset: 8 * 16,
+                size: 2 * 16,
+            }
+        );
+        // 2x1 and 1x1 still occupy a full block each
+        assert_eq!(
+            mips.iter().map(|m| (m.width, m.height)).collect::<Vec<_>>(),
+            [(16, 8), (8, 4), (4, 2), (2, 1), (1, 1)]
+        );
+        assert_eq!(
+            mips.iter().map(|m| m.offset).collect::<Vec<_>>(),
+            [0, 128, 160, 176, 192]
+        );
+        assert_eq!(texture.mip_level(4), Some(mips[4]));
+        assert_eq!(texture.mip_level(5), None);
+    }
+
+    #[test]
+    fn test_unaligned_mip_levels() {
+        let texture = DdsTexture::new(new_texture(30, 30, 5, DxgiFormat::BC7_UNorm));
+        assert_eq!(
+            texture
+                .mip_levels()
+                .iter()
+                .map(|m| (m.width, m.blocks_x, m.offset / 16))
+                .collect::<Vec<_>>(),
+            [(30, 8, 0), (15, 4, 64), (7, 2, 80), (3, 1, 84), (1, 1, 85)]
+        );
+    }
+
+    #[test]
+    fn test_decode_bc1() {
+        let mut dds = new_texture(8, 4, 2, DxgiFormat::BC1_UNorm_sRGB);
+        // Two solid red blocks and a black to white gradient block for the second mip
+        let data = [
+            [0x00, 0xf8, 0x00, 0xf8, 0, 0, 0, 0],
+            [0x00, 0xf8, 0x00, 0xf8, 0, 0, 0, 0],
+            [0xff, 0xff, 0x00, 0x00, 0xe4, 0xe4, 0xe4, 0xe4],
+        ]
+        .concat();
+        dds.data.copy_from_slice(&data);
+        let texture = DdsTexture::new(dds);
+        assert_eq!(texture.mip_level(1).unwrap().offset, 16);
+
+        let top = texture.decode_mip(0, 0).unwrap();
+        assert!(top.pixels().all(|p| p.0 == [255, 0, 0, 255]));
+
+        let second = texture.decode_mip(0, 1).unwrap();
+        assert_eq!(second.dimensions(), (4, 2));
+        assert_eq!(second.get_pixel(3, 1).0, [85, 85, 85, 255]);
+    }
+
+    #[test]
+    fn test_decode_bc4_bc5() {
+        // Solid 0x80 block, signed that's -128 which clamps to -1.0
+        let bc4_block = [0x80, 0x80, 0, 0, 0, 0, 0, 0];
+
+        let mut dds = new_texture(4, 4, 1, DxgiFormat::BC4_UNorm);
+        dds.data.copy_from_slice(&bc4_block);
+        let img = DdsTexture::new(dds).decode_image(0, 0).unwrap();
+        assert!(img.as_luma8().unwrap().pixels().all(|p| p.0 == [0x80]));
+
+        let mut dds = new_texture(4, 4, 1, DxgiFormat::BC4_SNorm);
+        dds.data.copy_from_slice(&bc4_block);
+        let img = DdsTexture::new(dds).decode_mip(0, 0).unwrap();
+        assert!(img.pixels().all(|p| p.0 == [0, 0, 0, 255]));
+
+        // X = 1.0, Y = 0.0 for signed, X = 1.0, Y ~= 0.0 for unsigned
+        let bc5_block = [[127, 127, 0, 0, 0, 0, 0, 0], [0; 8]].concat();
+        let mut dds = new_texture(4, 4, 1, DxgiFormat::BC5_SNorm);
+        dds.data.copy_from_slice(&bc5_block);
+        let texture = DdsTexture::new(dds);
+        let img = texture.decode_image(0, 0).unwrap();
+        assert_eq!(img.as_rgb8().unwrap().get_pixel(0, 0).0, [255, 127, 0]);
+        let normals = texture.decode_normal_map(0, 0).unwrap();
+        assert_eq!(normals.get_pixel(0, 0).0, [255, 128, 128]);
+
+        let bc5_block = [[255, 255, 0, 0, 0, 0, 0, 0], [128, 128, 0, 0, 0, 0, 0, 0]].concat();
+        let mut dds = new_texture(4, 4, 1, DxgiFormat::BC5_UNorm);
+        dds.data.copy_from_slice(&bc5_block);
+        let texture = DdsTexture::new(dds);
+        let normals = texture.decode_normal_map(0, 0).unwrap();
+        assert_eq!(normals.get_pixel(3, 3).0, [255, 128, 128]);
+
+        let texture = DdsTexture::new(new_texture(4, 4, 1, DxgiFormat::BC1_UNorm));
+        assert!(matches!(
+            texture.decode_normal_map(0, 0),
+            Err(DecodeError::UnsupportedFormat(Some(DxgiFormat::BC1_UNorm)))
+        ));
+    }
+
+    #[test]
+    fn test_decode_bc6h() {
+        // Mode 11 block with both endpoints at r = 512 of 1023, g = 0, b = 0
+        let fields = [
+            (0b00011, 5),
+            (512, 10),
+            (0, 20),
+            (512, 10),
+            (0, 20),
+            (0, 63),
+        ];
+        let mut dds = new_texture(4, 4, 1, DxgiFormat::BC6H_UF16);
+        dds.data = build_block(&fields).to_vec();
+        let texture = DdsTexture::new(dds);
+
+        let hdr = texture.decode_hdr(0, 0).unwrap();
+        assert!(hdr.pixels().all(|p| p.0 == [1.5146484, 0.0, 0.0]));
+
+        let img = texture.decode_image(0, 0).unwrap();
+        assert!(img.as_rgb32f().is_some());
+
+        // One stop down and clamped, 0.757 in linear is 226 in sRGB
+        let options = HdrOptions {
+            exposure: -1.0,
+            tonemap: Tonemap::Clamp,
+        };
+        let img = texture.decode_tonemapped(0, 0, options).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, [226, 0, 0]);
+        let [r, g, b] = HdrOptions::default().apply([1.5146484, 0.0, 0.0]);
+        assert_eq!(
+            texture.decode_mip(0, 0).unwrap().get_pixel(0, 0).0,
+            [r, g, b, 255]
+        );
+    }
+
+    #[test]
+    fn test_decode_float() {
+        let mut dds = new_texture(2, 1, 1, DxgiFormat::R16G16B16A16_Float);
+        dds.data = [4.0f32, 0.5, 0.0, 1.0, -1.0, 1.0, 0.25, 0.5]
+            .map(|v| half::f16::from_f32(v).to_le_bytes())
+            .concat();
+        let texture = DdsTexture::new(dds);
+
+        let img = texture.decode_float(0, 0).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, [4.0, 0.5, 0.0, 1.0]);
+        assert_eq!(img.get_pixel(1, 0).0, [-1.0, 1.0, 0.25, 0.5]);
+        assert!(texture.decode_image(0, 0).unwrap().as_rgba32f().is_some());
+
+        // Legacy float FourCCs decode too
+        let mut legacy = new_texture(1, 1, 1, DxgiFormat::R32_Float);
+        legacy.header10 = None;
+        legacy.header.spf = PixelFormat {
+            flags: PixelFormatFlags::FOURCC,
+            fourcc: Some(FourCC(FourCC::R32F)),
+            ..Default::default()
+        };
+        legacy.data = 2.5f32.to_le_bytes().to_vec();
+        let img = DdsTexture::new(legacy).decode_hdr(0, 0).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, [2.5; 3]);
+
+        // Shared exponent HDR goes through the same RGB float path as BC6H
+        let mut shared = new_texture(1, 1, 1, DxgiFormat::R9G9B9E5_SharedExp);
+        shared.data = (384u32 | (17 << 27)).to_le_bytes().to_vec();
+        let shared = DdsTexture::new(shared);
+        let img = shared.decode_image(0, 0).unwrap();
+        assert_eq!(img.as_rgb32f().unwrap().get_pixel(0, 0).0, [3.0, 0.0, 0.0]);
+
+        let options = HdrOptions {
+            exposure: 0.0,
+            tonemap: Tonemap::Clamp,
+        };
+        let img = texture.decode_tonemapped(0, 0, options).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, [255, 188, 0]);
+        let [r, g, b] = HdrOptions::default().apply([4.0, 0.5, 0.0]);
+        let img = texture.decode_mip(0, 0).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, [r, g, b, 255]);
+        assert_eq!(img.get_pixel(1, 0)[3], 128);
+
+        // 10-bit UNORM keeps its precision as 16-bit, and 8-bit decodes use the top bits
+        let mut dds = new_texture(1, 1, 1, DxgiFormat::R10G10B10A2_UNorm);
+        dds.data = (0x3ffu32 | (1 << 10) | (3 << 30)).to_le_bytes().to_vec();
+        let texture = DdsTexture::new(dds);
+        let img = texture.decode_image(0, 0).unwrap();
+        assert_eq!(
+            img.as_rgba16().unwrap().get_pixel(0, 0).0,
+            [65535, 64, 0, 65535]
+        );
+        assert_eq!(
+            texture.decode_mip(0, 0).unwrap().get_pixel(0, 0).0,
+            [255, 0, 0, 255]
+        );
+        // UNORM values aren't linear, so they aren't tonemapped or sRGB encoded
+        assert!(matches!(
+            texture.decode_tonemapped(0, 0, options),
+            Err(DecodeError::UnsupportedFormat(Some(
+                DxgiFormat::R10G10B10A2_UNorm
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_decode_visualized() {
+        let mut dds = new_texture(3, 1, 1, DxgiFormat::R16_UInt);
+        dds.data = [0u16, 10, 40]
+            .into_iter()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let texture = DdsTexture::new(dds);
+
+        // The full 16-bit range leaves small IDs nearly black
+        let img = texture.decode_mip(0, 0).unwrap();
+        assert_eq!(img.get_pixel(2, 0).0, [0, 0, 0, 255]);
+        let img = texture
+            .decode_visualized(0, 0, Visualization::Normalize)
+            .unwrap();
+        assert_eq!(img.get_pixel(1, 0).0, [64, 64, 64, 255]);
+        assert_eq!(img.get_pixel(2, 0).0, [255, 255, 255, 255]);
+        let img = texture
+            .decode_visualized(0, 0, Visualization::FalseColour)
+            .unwrap();
+        assert_ne!(img.get_pixel(1, 0), img.get_pixel(2, 0));
+
+        let texture = DdsTexture::new(new_texture(4, 4, 1, DxgiFormat::BC7_UNorm));
+        assert!(matches!(
+            texture.decode_visualized(0, 0, Visualization::Remap),
+            Err(DecodeError::UnsupportedFormat(Some(DxgiFormat::BC7_UNorm)))
+        ));
+    }
+
+    #[test]
+    fn test_decode_uncompressed() {
+        let mut dds = new_texture(3, 2, 2, DxgiFormat::B8G8R8A8_UNorm);
+        dds.data = (0..28).collect();
+        let texture = DdsTexture::new(dds);
+
+        // One row of pixels counts as one row of blocks
+        let mip_levels = texture
+            .mip_levels()
+            .iter()
+            .map(|m| (m.blocks_x, m.row_pitch, m.offset, m.size))
+            .collect::<Vec<_>>();
+        assert_eq!(mip_levels, [(3, 12, 0, 24), (1, 4, 24, 4)]);
+
+        let top = texture.decode_mip(0, 0).unwrap();
+        assert_eq!(top.get_pixel(0, 0).0, [2, 1, 0, 3]);
+        assert_eq!(top.get_pixel(2, 1).0, [22, 21, 20, 23]);
+        let second = texture.decode_mip(0, 1).unwrap();
+        assert_eq!(second.get_pixel(0, 0).0, [26, 25, 24, 27]);
+    }
+
+    #[test]
+    fn test_decode_legacy_16_bit() {
+        let data = [0x7c00u16, 0x801f]
+            .into_iter()
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        let dds = new_legacy_texture(D3DFormat::A1R5G5B5, 2, 1, data.clone());
+        let texture = DdsTexture::new(dds);
+        assert_eq!(texture.format(), Some(DxgiFormat::B5G5R5A1_UNorm));
+        let img = texture.decode_mip(0, 0).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 0]);
+        assert_eq!(img.get_pixel(1, 0).0, [0, 0, 255, 255]);
+
+        // Same bits, but the top bit is padding
+        let dds = new_legacy_texture(D3DFormat::X1R5G5B5, 2, 1, data);
+        let img = DdsTexture::new(dds).decode_mip(0, 0).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(img.get_pixel(1, 0).0, [0, 0, 255, 255]);
+    }
+
+    #[test]
+    fn test_decode_luminance() {
+        let mut dds = new_legacy_texture(D3DFormat::A8L8, 2, 1, vec![10, 20, 30, 40]);
+        // ddsfile writes luminance formats as RGB, unlike real files
+        dds.header.spf = PixelFormat {
+            flags: PixelFormatFlags::LUMINANCE | PixelFormatFlags::ALPHA_PIXELS,
+            rgb_bit_count: Some(16),
+            a_bit_mask: Some(0xff00),
+            ..Default::default()
+        };
+        let texture = DdsTexture::new(dds);
+        assert_eq!(texture.format(), None);
+        assert_eq!(texture.legacy_format(), Some(LegacyFormat::A8L8));
+
+        assert_eq!(
+            texture.decode_mip(0, 0).unwrap().get_pixel(1, 0).0,
+            [30, 30, 30, 40]
+        );
+        let img = texture.decode_image(0, 0).unwrap();
+        assert_eq!(img.as_luma_alpha8().unwrap().get_pixel(0, 0).0, [10, 20]);
+    }
+
+    #[test]
+    fn test_decode_bit_masks() {
+        let data = [0x3ff00000u32, 0xc00003ff]
+            .into_iter()
+            .flat_map(u32::to_le_bytes)
+            .collect();
+        let dds = new_legacy_texture(D3DFormat::A2R10G10B10, 2, 1, data);
+        let texture = DdsTexture::new(dds);
+        assert!(matches!(
+            texture.legacy_format(),
+            Some(LegacyFormat::Masked(_))
+        ));
+
+        let img = texture.decode_mip(0, 0).unwrap();
+        assert_eq!(img.get_pixel(0, 0).0, [255, 0, 0, 0]);
+        assert_eq!(img.get_pixel(1, 0).0, [0, 0, 255, 255]);
+        let img = texture.decode_image(0, 0).unwrap();
+        assert_eq!(
+            img.as_rgba16().unwrap().get_pixel(1, 0).0,
+            [0, 0, 0xffff, 0xffff]
+        );
+
+        // Without an alpha mask, wide channels still come out opaque
+        let data = 0x3ff00000u32.to_le_bytes().to_vec();
+        let mut dds = new_legacy_texture(D3DFormat::A2R10G10B10, 1, 1, data);
+        // X2R10G10B10, which ddsfile has no constant for
+        dds.header.spf.flags.remove(PixelFormatFlags::ALPHA_PIXELS);
+        dds.header.spf.a_bit_mask = None;
+        let img = DdsTexture::new(dds).decode_image(0, 0).unwrap();
+        assert_eq!(
+            img.as_rgba16().unwrap().get_pixel(0, 0).0,
+            [0xffff, 0, 0, 0xffff]
+        );
+    }
+
+    #[test]
+    fn test_read_palettized() {
+        let dds = new_legacy_texture(D3DFormat::L8, 3, 1, Vec::new());
+        let mut bytes = Vec::new();
+        dds.write(&mut bytes).unwrap();
+
+        // Turn it into P8, with palette entry i set to (i, 2i, 3i, 255)
+        bytes[80..84].copy_from_slice(&0x20u32.to_le_bytes());
+        bytes.truncate(128);
+        bytes.extend((0..=255u8).flat_map(|i| [i, i.wrapping_mul(2), i.wrapping_mul(3), 255]));
+        bytes.extend([0, 1, 100]);
+
+        let texture = DdsTexture::read(bytes.as_slice()).unwrap();
+        assert_eq!(texture.legacy_format(), Some(LegacyFormat::P8));
+        assert_eq!(texture.palette().map(<[_]>::len), Some(256));
+        let img = texture.decode_mip(0, 0).unwrap();
+        assert_eq!(img.get_pixel(1, 0).0, [1, 2, 3, 255]);
+        assert_eq!(img.get_pixel(2, 0).0, [100, 200, 44, 255]);
+
+        // Missing palette
+        bytes.truncate(128 + 100);
+        assert!(matches!(
+            DdsTexture::read(bytes.as_slice()),
+            Err(DecodeError::TruncatedData {
+                expected: 1024,
+                actual: 100
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_row_pitch() {
+        // 3 pixel rows padded out to 4 bytes
+        let mut dds = new_texture(3, 2, 1, DxgiFormat::R8_UNorm);
+        dds.header.pitch = Some(4);
+        dds.data = vec![1, 2, 3, 0, 4, 5, 6, 0];
+        let texture = DdsTexture::new(dds);
+        assert_eq!(texture.mip_level(0).unwrap().row_pitch, 4);
+
+        let img = texture.decode_image(0, 0).unwrap().into_luma8();
+        assert_eq!(img.into_raw(), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_zero_mip_count() {
+        let mut dds = new_texture(4, 4, 1, DxgiFormat::BC7_UNorm);
+        dds.header.mip_map_count = Some(0);
+        dds.data = solid_bc7_block(0x33).to_vec();
+        let texture = DdsTexture::new(dds);
+        assert_eq!(texture.mip_count(), 1);
+        assert_eq!(
+            texture.decode_mip(0, 0).unwrap().get_pixel(0, 0).0,
+            [0x33, 0x33, 0x33, 0xff]
+        );
+    }
+
+    #[test]
+    fn test_decode_surface_size_pitch() {
+        // Some writers store the size of the whole surface as the pitch
+        let mut dds = new_texture(4, 4, 1, DxgiFormat::R8G8B8A8_UNorm);
+        dds.header.pitch = Some(64);
+        dds.data = (0..64).collect();
+        let texture = DdsTexture::new(dds);
+        assert_eq!(texture.mip_level(0).unwrap().row_pitch, 16);
+
+        let img = texture.decode_mip(0, 0).unwrap();
+        assert_eq!(img.get_pixel(3, 3).0, [60, 61, 62, 63]);
+    }
+
+    #[test]
+    fn test_decode_padded_mips() {
+        // 24-bit pixels with DWORD aligned rows on every level and layer
+        let new_rgb = |data| {
+            let mut dds = new_legacy_texture(D3DFormat::R8G8B8, 3, 2, data);
+            dds.header.mip_map_count = Some(2);
+            dds
+        };
+        // Level 0 is 2 rows of 9 bytes padded to 12, level 1 a single 3 byte row padded to 4
+        let mut dds = new_rgb((0..28).collect());
+        dds.header.pitch = Some(12);
+        let texture = DdsTexture::new(dds);
+        let mip_levels = texture
+            .mip_levels()
+            .iter()
+            .map(|m| (m.row_pitch, m.offset, m.size))
+            .collect::<Vec<_>>();
+        assert_eq!(mip_levels, [(12, 0, 24), (4, 24, 4)]);
+        let second = texture.decode_mip(0, 1).unwrap();
+        assert_eq!(second.get_pixel(0, 0).0, [26, 25, 24, 255]);
+
+        // Padding that no alignment produces, with too little data for unpadded rows
+        let mut dds = new_rgb(vec![0; 20]);
+        dds.header.pitch = Some(11);
+        assert!(matches!(
+            DdsTexture::new(dds).decode_mip(0, 1),
+            Err(DecodeError::UnsupportedPitch {
+                pitch: 11,
+                row_size: 9
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_unaligned() {
+        // 2x2 blocks, with the right column and bottom row only partially used
+        let mut dds = new_texture(6, 5, 1, DxgiFormat::BC7_UNorm);
+        let data = [0x11, 0x33, 0x55, 0x77]
+            .into_iter()
+            .flat_map(solid_bc7_block)
+            .collect::<Vec<_>>();
+        dds.data.copy_from_slice(&data);
+        let texture = DdsTexture::new(dds);
+
+        let img = texture.decode_mip(0, 0).unwrap();
+        assert_eq!(img.dimensions(), (6, 5));
+        assert_eq!(img.get_pixel(3, 3).0, [0x11, 0x11, 0x11, 0xff]);
+        assert_eq!(img.get_pixel(5, 0).0, [0x33, 0x33, 0x33, 0xff]);
+        assert_eq!(img.get_pixel(0, 4).0, [0x55, 0x55, 0x55, 0xff]);
+        assert_eq!(img.get_pixel(5, 4).0, [0x77, 0x77, 0x77, 0xff]);
+    }
+
+    #[test]
+    fn test_decode_mips() {
+        let mut dds = new_texture(8, 8, 4, DxgiFormat::BC7_UNorm);
+        let data = [0x11, 0x33, 0x55, 0x77]
+            .into_iter()
+            .zip([4, 1, 1, 1])
+            .flat_map(|(value, blocks)| (0..blocks).map(move |_| solid_bc7_block(value)))
+            .flatten()
+            .collect::<Vec<_>>();
+        dds.data.copy_from_slice(&data);
+        let texture = DdsTexture::new(dds);
+
+        let mips = texture.decode_mips(0).unwrap();
+        assert_eq!(
+            mips.iter().map(|m| m.dimensions()).collect::<Vec<_>>(),
+            [(8, 8), (4, 4), (2, 2), (1, 1)]
+        );
+        mips.iter()
+            .zip([0x11, 0x33, 0x55, 0x77])
+            .for_each(|(mip, v)| {
+                assert!(mip.pixels().all(|p| p.0 == [v, v, v, 0xff]));
+            });
+    }
+
+    #[test]
+    fn test_decode_cube() {
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            array_layers: Some(6),
+            caps2: Some(Caps2::CUBEMAP | Caps2::CUBEMAP_ALLFACES),
+            is_cubemap: true,
+            ..texture_params(4, 4, 1, DxgiFormat::BC7_UNorm)
+        })
+        .unwrap();
+        let values = [0x11, 0x33, 0x55, 0x77, 0x99, 0xbb];
+        let data = values
+            .into_iter()
+            .flat_map(solid_bc7_block)
+            .collect::<Vec<_>>();
+        dds.data.copy_from_slice(&data);
+        let texture = DdsTexture::new(dds);
+
+        assert!(texture.is_cubemap());
+        assert_eq!(texture.array_layers(), 6);
+        assert_eq!(texture.cube_count(), 1);
+
+        let faces = texture.decode_cube_faces(0, 0).unwrap();
+        faces.iter().zip(values).for_each(|(face, v)| {
+            assert_eq!(face.get_pixel(0, 0).0, [v, v, v, 0xff]);
+        });
+
+        let cross = texture
+            .decode_cube(0, 0, CubeLayout::HorizontalCross)
+            .unwrap();
+        assert_eq!(cross[0].dimensions(), (16, 12));
+        // +Z sits in the middle of the cross
+        assert_eq!(cross[0].get_pixel(4, 4).0, [0x99, 0x99, 0x99, 0xff]);
+
+        let dir = std::env::temp_dir().join(format!("dds_parser_cube_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let paths = texture
+            .export_layers(0, dir.join("sky.png"), ArrayLayout::Separate)
+            .unwrap();
+        assert_eq!(paths[0], dir.join("sky_0_+x.png"));
+        assert_eq!(paths[5], dir.join("sky_0_-z.png"));
+        std::fs::remove_dir_all(dir).unwrap();
+
+        assert!(matches!(
+            texture.decode_cube_faces(1, 0),
+            Err(DecodeError::OutOfBounds { .. })
+        ));
+    }
+
+    #[test]
+    fn test_array_layers() {
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            array_layers: Some(3),
+            ..texture_params(4, 4, 2, DxgiFormat::BC7_UNorm)
+        })
+        .unwrap();
+        // Each layer holds its own two mips
+        let values = [0x11, 0x33, 0x55, 0x77, 0x99, 0xbb];
+        dds.data = values.into_iter().flat_map(solid_bc7_block).collect();
+        let texture = DdsTexture::new(dds);
+        assert_eq!(texture.array_layers(), 3);
+
+        let subresources = texture.subresources().collect::<Vec<_>>();
+        assert_eq!(
+            subresources
+                .iter()
+                .map(|Subresource { layer, mip }| (*layer, mip.level))
+                .collect::<Vec<_>>(),
+            [(0, 0), (0, 1), (1, 0), (1, 1), (2, 0), (2, 1)]
+        );
+        texture
+            .decode_subresources()
+            .zip(values)
+            .for_each(|((_, img), v)| {
+                assert_eq!(img.unwrap().get_pixel(0, 0).0, [v, v, v, 0xff]);
+            });
+
+        let dir = std::env::temp_dir().join(format!("dds_parser_layers_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let paths = texture
+            .export_layers(1, dir.join("layer.png"), ArrayLayout::Separate)
+            .unwrap();
+        assert_eq!(
+            paths,
+            ["layer_0.png", "layer_1.png", "layer_2.png"].map(|name| dir.join(name))
+        );
+        let img = image::open(&paths[2]).unwrap().into_rgba8();
+        assert_eq!(img.get_pixel(0, 0).0, [0xbb, 0xbb, 0xbb, 0xff]);
+
+        let paths = texture
+            .export_layers(0, dir.join("grid.png"), ArrayLayout::Grid { columns: 2 })
+            .unwrap();
+        let img = image::open(&paths[0]).unwrap().into_rgba8();
+        assert_eq!(img.dimensions(), (8, 8));
+        assert_eq!(img.get_pixel(4, 0).0, [0x55, 0x55, 0x55, 0xff]);
+        assert_eq!(img.get_pixel(4, 4).0, [0, 0, 0, 0]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_decode_volume() {
+        let mut dds = Dds::new_dxgi(NewDxgiParams {
+            depth: Some(4),
+            caps2: Some(Caps2::VOLUME),
+            resource_dimension: D3D10ResourceDimension::Texture3D,
+            ..texture_params(4, 8, 2, DxgiFormat::BC7_UNorm)
+        })
+        .unwrap();
+        // Top level: 4 slices of 1x2 blocks, then 2 slices of a single block
+        let values = [0x11, 0x33, 0x55, 0x77, 0x99, 0xbb];
+        dds.data = values
+            .iter()
+            .flat_map(|&v| (0..2).map(move |_| solid_bc7_block(v)))
+            .take(8)
+            .chain(values[4..].iter().map(|&v| solid_bc7_block(v)))
+            .flatten()
+            .collect();
+        let texture = DdsTexture::new(dds);
+
+        assert!(texture.is_volume());
+        assert_eq!(
+            texture
+                .mip_levels()
+                .iter()
+                .map(|m| (m.width, m.height, m.depth, m.offset, m.slice_size()))
+                .collect::<Vec<_>>(),
+            [(4, 8, 4, 0, 32), (2, 4, 2, 128, 16)]
+        );
+
+        let slices = texture.decode_slices(0).unwrap();
+        assert_eq!(slices.len(), 4);
+        slices.iter().zip(values).for_each(|(slice, v)| {
+            assert_eq!(slice.dimensions(), (4, 8));
+            assert!(slice.pixels().all(|p| p.0 == [v, v, v, 0xff]));
+        });
+
+        let slice = texture.decode_slice(1, 1).unwrap();
+        assert_eq!(slice.dimensions(), (2, 4));
+        assert_eq!(slice.get_pixel(0, 0).0, [0xbb, 0xbb, 0xbb, 0xff]);
+
+        let atlas = texture.decode_volume_atlas(0, 2).unwrap();
+        assert_eq!(atlas.dimensions(), (8, 16));
+        assert_eq!(atlas.get_pixel(7, 15).0, [0x77, 0x77, 0x77, 0xff]);
+
+        assert!(matches!(
+            texture.decode_slice(1, 2),
+            Err(DecodeError::SliceOutOfBounds { slice: 2, depth: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_decode_errors() {
+        let texture = DdsTexture::new(new_texture(8, 8, 1, DxgiFormat::BC7_UNorm));
+        assert!(matches!(
+            texture.decode_mip(1, 0),
+            Err(DecodeError::OutOfBounds { layer: 1, level: 0 })
+        ));
+        assert!(matches!(
+            texture.decode_mip(0, 1),
+            Err(DecodeError::OutOfBounds { layer: 0, level: 1 })
+        ));
+
+        let texture = DdsTexture::new(new_texture(8, 8, 1, DxgiFormat::D24_UNorm_S8_UInt));
+        assert!(matches!(
+            texture.decode_mip(0, 0),
+            Err(DecodeError::UnsupportedFormat(Some(
+                DxgiFormat::D24_UNorm_S8_UInt
+            )))
+        ));
+    }
+}
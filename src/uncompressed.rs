@@ -0,0 +1,470 @@
+use ddsfile::DxgiFormat;
+use half::f16;
+
+use crate::pixel::{replicate_bits, RGBA};
+
+/// Converts the bytes of a single pixel into RGBA
+pub type PixelDecoder = fn(&[u8]) -> RGBA;
+
+/// Converts the bytes of a single pixel into RGBA floats
+pub type FloatPixelDecoder = fn(&[u8]) -> [f32; 4];
+
+/// Bytes per pixel and decoder for an uncompressed format, if it's supported.
+/// sRGB variants decode the same way, leaving the stored values as they are.
+/// Single channel formats come out grey, two channel formats as red and green
+pub fn pixel_codec(format: DxgiFormat) -> Option<(usize, PixelDecoder)> {
+    let codec: (usize, PixelDecoder) = match format {
+        DxgiFormat::R8G8B8A8_UNorm | DxgiFormat::R8G8B8A8_UNorm_sRGB => (4, |p| RGBA {
+            r: p[0],
+            g: p[1],
+            b: p[2],
+            a: p[3],
+        }),
+        DxgiFormat::B8G8R8A8_UNorm | DxgiFormat::B8G8R8A8_UNorm_sRGB => (4, |p| RGBA {
+            r: p[2],
+            g: p[1],
+            b: p[0],
+            a: p[3],
+        }),
+        // The X byte is padding, not alpha
+        DxgiFormat::B8G8R8X8_UNorm | DxgiFormat::B8G8R8X8_UNorm_sRGB => (4, |p| RGBA {
+            r: p[2],
+            g: p[1],
+            b: p[0],
+            a: 255,
+        }),
+        DxgiFormat::R8G8_UNorm => (2, |p| RGBA {
+            r: p[0],
+            g: p[1],
+            b: 0,
+            a: 255,
+        }),
+        DxgiFormat::R8_UNorm => (1, |p| RGBA {
+            r: p[0],
+            g: p[0],
+            b: p[0],
+            a: 255,
+        }),
+        DxgiFormat::A8_UNorm => (1, |p| RGBA {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: p[0],
+        }),
+        DxgiFormat::B5G6R5_UNorm => (2, |p| {
+            let [r, g, b] = unpack_16(p, [11, 5, 0], [5, 6, 5]);
+            RGBA { r, g, b, a: 255 }
+        }),
+        DxgiFormat::B5G5R5A1_UNorm => (2, |p| {
+            let [r, g, b, a] = unpack_16(p, [10, 5, 0, 15], [5, 5, 5, 1]);
+            RGBA { r, g, b, a }
+        }),
+        DxgiFormat::B4G4R4A4_UNorm => (2, |p| {
+            let [r, g, b, a] = unpack_16(p, [8, 4, 0, 12], [4, 4, 4, 4]);
+            RGBA { r, g, b, a }
+        }),
+        // Keeps the top 8 of each 10 bits, see float_pixel_codec for full precision
+        DxgiFormat::R10G10B10A2_UNorm => (4, |p| {
+            let [r, g, b, a] = unpack_1010102(p);
+            RGBA {
+                r: (r >> 2) as u8,
+                g: (g >> 2) as u8,
+                b: (b >> 2) as u8,
+                a: replicate_bits(a, 2, 8) as u8,
+            }
+        }),
+        _ => return None,
+    };
+
+    Some(codec)
+}
+
+/// Bytes per pixel and decoder for a format with more than 8 bits of precision per channel,
+/// if it's supported. Missing channels are 0, or 1.0 for alpha, and single channel formats
+/// come out grey
+pub fn float_pixel_codec(format: DxgiFormat) -> Option<(usize, FloatPixelDecoder)> {
+    let codec: (usize, FloatPixelDecoder) = match format {
+        DxgiFormat::R10G10B10A2_UNorm => (4, |p| {
+            let [r, g, b, a] = unpack_1010102(p);
+            [
+                r as f32 / 1023.0,
+                g as f32 / 1023.0,
+                b as f32 / 1023.0,
+                a as f32 / 3.0,
+            ]
+        }),
+        DxgiFormat::R11G11B10_Float => (4, |p| {
+            let value = u32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+            [
+                unpack_unsigned_float(value & 0x7ff, 6),
+                unpack_unsigned_float((value >> 11) & 0x7ff, 6),
+                unpack_unsigned_float(value >> 22, 5),
+                1.0,
+            ]
+        }),
+        // Three 9 bit mantissas sharing a 5 bit exponent, with no implicit leading 1
+        DxgiFormat::R9G9B9E5_SharedExp => (4, |p| {
+            let value = u32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+            let scale = ((value >> 27) as f32 - 15.0 - 9.0).exp2();
+            [
+                (value & 0x1ff) as f32 * scale,
+                ((value >> 9) & 0x1ff) as f32 * scale,
+                ((value >> 18) & 0x1ff) as f32 * scale,
+                1.0,
+            ]
+        }),
+        DxgiFormat::R16G16B16A16_Float => (8, |p| {
+            let mut rgba = [0.0; 4];
+            rgba.iter_mut().enumerate().for_each(|(i, c)| {
+                *c = f16::from_le_bytes([p[i * 2], p[i * 2 + 1]]).to_f32();
+            });
+            rgba
+        }),
+        DxgiFormat::R16_Float => (2, |p| {
+            let v = f16::from_le_bytes([p[0], p[1]]).to_f32();
+            [v, v, v, 1.0]
+        }),
+        DxgiFormat::R16G16_Float => (4, |p| {
+            let r = f16::from_le_bytes([p[0], p[1]]).to_f32();
+            let g = f16::from_le_bytes([p[2], p[3]]).to_f32();
+            [r, g, 0.0, 1.0]
+        }),
+        DxgiFormat::R32_Float => (4, |p| {
+            let v = f32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+            [v, v, v, 1.0]
+        }),
+        DxgiFormat::R32G32_Float => (8, |p| {
+            let r = f32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+            let g = f32::from_le_bytes([p[4], p[5], p[6], p[7]]);
+            [r, g, 0.0, 1.0]
+        }),
+        DxgiFormat::R32G32B32A32_Float => (16, |p| {
+            let mut rgba = [0.0; 4];
+            rgba.iter_mut().enumerate().for_each(|(i, c)| {
+                *c = f32::from_le_bytes([p[i * 4], p[i * 4 + 1], p[i * 4 + 2], p[i * 4 + 3]]);
+            });
+            rgba
+        }),
+        _ => return None,
+    };
+
+    Some(codec)
+}
+
+/// How the stored bits of a channel are interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    UNorm,
+    SNorm,
+    UInt,
+    SInt,
+}
+
+/// Layout of a format whose channels all share the same width and kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelLayout {
+    pub kind: ChannelKind,
+    /// Number of channels, from 1 to 4
+    pub channels: usize,
+    /// Bytes per channel, 1, 2 or 4
+    pub channel_size: usize,
+}
+
+impl ChannelLayout {
+    /// Bytes per pixel
+    pub fn pixel_size(&self) -> usize {
+        self.channels * self.channel_size
+    }
+
+    /// Lowest and highest value a channel can hold, after normalisation for UNORM and SNORM
+    pub fn range(&self) -> (f64, f64) {
+        let bits = self.channel_size as u32 * 8;
+        match self.kind {
+            ChannelKind::UNorm => (0.0, 1.0),
+            ChannelKind::SNorm => (-1.0, 1.0),
+            ChannelKind::UInt => (0.0, ((1u64 << bits) - 1) as f64),
+            ChannelKind::SInt => (
+                -((1u64 << (bits - 1)) as f64),
+                ((1u64 << (bits - 1)) - 1) as f64,
+            ),
+        }
+    }
+
+    /// Values of each channel, with normalised formats brought into [0, 1] or [-1, 1] and
+    /// integers kept as they are. Channels the format doesn't store are 0
+    pub fn decode_pixel(&self, p: &[u8]) -> [f64; 4] {
+        let bits = self.channel_size as u32 * 8;
+        let mut values = [0.0; 4];
+        p.chunks_exact(self.channel_size)
+            .take(self.channels)
+            .zip(values.iter_mut())
+            .for_each(|(c, v)| {
+                let mut bytes = [0; 4];
+                bytes[..c.len()].copy_from_slice(c);
+                let unsigned = u32::from_le_bytes(bytes);
+                // Move the sign bit to the top before shifting back down
+                let signed = ((unsigned << (32 - bits)) as i32 >> (32 - bits)) as f64;
+                let max_signed = ((1u64 << (bits - 1)) - 1) as f64;
+
+                *v = match self.kind {
+                    ChannelKind::UNorm => unsigned as f64 / ((1u64 << bits) - 1) as f64,
+                    // The most negative value is clamped to -1.0 like the one above it
+                    ChannelKind::SNorm => (signed / max_signed).max(-1.0),
+                    ChannelKind::UInt => unsigned as f64,
+                    ChannelKind::SInt => signed,
+                };
+            });
+
+        values
+    }
+}
+
+/// Channel layout of the UNORM, SNORM, UINT and SINT formats, if it's supported
+pub fn channel_layout(format: DxgiFormat) -> Option<ChannelLayout> {
+    let (kind, channels, channel_size) = match format {
+        DxgiFormat::R8_UNorm => (ChannelKind::UNorm, 1, 1),
+        DxgiFormat::R8G8_UNorm => (ChannelKind::UNorm, 2, 1),
+        DxgiFormat::R8G8B8A8_UNorm => (ChannelKind::UNorm, 4, 1),
+        DxgiFormat::R16_UNorm => (ChannelKind::UNorm, 1, 2),
+        DxgiFormat::R16G16_UNorm => (ChannelKind::UNorm, 2, 2),
+        DxgiFormat::R16G16B16A16_UNorm => (ChannelKind::UNorm, 4, 2),
+        DxgiFormat::R8_SNorm => (ChannelKind::SNorm, 1, 1),
+        DxgiFormat::R8G8_SNorm => (ChannelKind::SNorm, 2, 1),
+        DxgiFormat::R8G8B8A8_SNorm => (ChannelKind::SNorm, 4, 1),
+        DxgiFormat::R16_SNorm => (ChannelKind::SNorm, 1, 2),
+        DxgiFormat::R16G16_SNorm => (ChannelKind::SNorm, 2, 2),
+        DxgiFormat::R16G16B16A16_SNorm => (ChannelKind::SNorm, 4, 2),
+        DxgiFormat::R8_UInt => (ChannelKind::UInt, 1, 1),
+        DxgiFormat::R8G8_UInt => (ChannelKind::UInt, 2, 1),
+        DxgiFormat::R8G8B8A8_UInt => (ChannelKind::UInt, 4, 1),
+        DxgiFormat::R16_UInt => (ChannelKind::UInt, 1, 2),
+        DxgiFormat::R16G16_UInt => (ChannelKind::UInt, 2, 2),
+        DxgiFormat::R16G16B16A16_UInt => (ChannelKind::UInt, 4, 2),
+        DxgiFormat::R32_UInt => (ChannelKind::UInt, 1, 4),
+        DxgiFormat::R32G32_UInt => (ChannelKind::UInt, 2, 4),
+        DxgiFormat::R32G32B32_UInt => (ChannelKind::UInt, 3, 4),
+        DxgiFormat::R32G32B32A32_UInt => (ChannelKind::UInt, 4, 4),
+        DxgiFormat::R8_SInt => (ChannelKind::SInt, 1, 1),
+        DxgiFormat::R8G8_SInt => (ChannelKind::SInt, 2, 1),
+        DxgiFormat::R8G8B8A8_SInt => (ChannelKind::SInt, 4, 1),
+        DxgiFormat::R16_SInt => (ChannelKind::SInt, 1, 2),
+        DxgiFormat::R16G16_SInt => (ChannelKind::SInt, 2, 2),
+        DxgiFormat::R16G16B16A16_SInt => (ChannelKind::SInt, 4, 2),
+        DxgiFormat::R32_SInt => (ChannelKind::SInt, 1, 4),
+        DxgiFormat::R32G32_SInt => (ChannelKind::SInt, 2, 4),
+        DxgiFormat::R32G32B32_SInt => (ChannelKind::SInt, 3, 4),
+        DxgiFormat::R32G32B32A32_SInt => (ChannelKind::SInt, 4, 4),
+        _ => return None,
+    };
+
+    Some(ChannelLayout {
+        kind,
+        channels,
+        channel_size,
+    })
+}
+
+/// Split a little endian 10:10:10:2 pixel into its R, G, B and A bits
+fn unpack_1010102(p: &[u8]) -> [u32; 4] {
+    let value = u32::from_le_bytes([p[0], p[1], p[2], p[3]]);
+    [
+        value & 0x3ff,
+        (value >> 10) & 0x3ff,
+        (value >> 20) & 0x3ff,
+        value >> 30,
+    ]
+}
+
+/// Decode the unsigned 11 and 10 bit floats of R11G11B10_FLOAT. Both have a 5 bit exponent
+/// with the same bias as half floats, followed by a 6 or 5 bit mantissa
+fn unpack_unsigned_float(bits: u32, mantissa_bits: u32) -> f32 {
+    let exponent = bits >> mantissa_bits;
+    let mantissa = (bits & ((1 << mantissa_bits) - 1)) as f32 / (1 << mantissa_bits) as f32;
+    match exponent {
+        // Denormals
+        0 => mantissa * (-14f32).exp2(),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + mantissa) * (exponent as f32 - 15.0).exp2(),
+    }
+}
+
+/// Pull channels out of a little endian 16 bit pixel, given each one's shift and width
+fn unpack_16<const N: usize>(p: &[u8], shifts: [u32; N], widths: [u32; N]) -> [u8; N] {
+    let value = u16::from_le_bytes([p[0], p[1]]) as u32;
+    let mut channels = [0; N];
+    channels.iter_mut().enumerate().for_each(|(i, c)| {
+        let bits = (value >> shifts[i]) & ((1 << widths[i]) - 1);
+        *c = replicate_bits(bits, widths[i], 8) as u8;
+    });
+
+    channels
+}
+
+#[cfg(test)]
+mod tests {
+    use ddsfile::DxgiFormat;
+
+    use super::{
+        channel_layout, float_pixel_codec, pixel_codec, unpack_unsigned_float, ChannelKind,
+    };
+
+    fn decode_float(format: DxgiFormat, bytes: &[u8]) -> [f32; 4] {
+        let (size, decode) = float_pixel_codec(format).unwrap();
+        assert_eq!(size, bytes.len());
+        decode(bytes)
+    }
+
+    fn decode(format: DxgiFormat, bytes: &[u8]) -> [u8; 4] {
+        let (size, decode) = pixel_codec(format).unwrap();
+        assert_eq!(size, bytes.len());
+        let p = decode(bytes);
+        [p.r, p.g, p.b, p.a]
+    }
+
+    #[test]
+    fn test_swizzles() {
+        let bytes = [1, 2, 3, 4];
+        assert_eq!(decode(DxgiFormat::R8G8B8A8_UNorm, &bytes), [1, 2, 3, 4]);
+        assert_eq!(
+            decode(DxgiFormat::B8G8R8A8_UNorm_sRGB, &bytes),
+            [3, 2, 1, 4]
+        );
+        assert_eq!(decode(DxgiFormat::B8G8R8X8_UNorm, &bytes), [3, 2, 1, 255]);
+        assert_eq!(decode(DxgiFormat::R8G8_UNorm, &bytes[..2]), [1, 2, 0, 255]);
+        assert_eq!(decode(DxgiFormat::R8_UNorm, &bytes[..1]), [1, 1, 1, 255]);
+        assert_eq!(decode(DxgiFormat::A8_UNorm, &bytes[..1]), [0, 0, 0, 1]);
+        assert!(pixel_codec(DxgiFormat::BC7_UNorm).is_none());
+    }
+
+    #[test]
+    fn test_packed_16() {
+        // White stays white
+        assert_eq!(decode(DxgiFormat::B5G6R5_UNorm, &[0xff, 0xff]), [255; 4]);
+        assert_eq!(decode(DxgiFormat::B5G5R5A1_UNorm, &[0xff, 0xff]), [255; 4]);
+        assert_eq!(decode(DxgiFormat::B4G4R4A4_UNorm, &[0xff, 0xff]), [255; 4]);
+
+        // Pure red, then pure green with alpha off, then one step of each channel
+        assert_eq!(
+            decode(DxgiFormat::B5G6R5_UNorm, &0xf800u16.to_le_bytes()),
+            [255, 0, 0, 255]
+        );
+        assert_eq!(
+            decode(DxgiFormat::B5G5R5A1_UNorm, &0x03e0u16.to_le_bytes()),
+            [0, 255, 0, 0]
+        );
+        assert_eq!(
+            decode(DxgiFormat::B4G4R4A4_UNorm, &0x1234u16.to_le_bytes()),
+            [0x22, 0x33, 0x44, 0x11]
+        );
+    }
+
+    #[test]
+    fn test_unsigned_float() {
+        // 1.0 is the bias as exponent and no mantissa, in both widths
+        assert_eq!(unpack_unsigned_float(15 << 6, 6), 1.0);
+        assert_eq!(unpack_unsigned_float(15 << 5, 5), 1.0);
+        assert_eq!(unpack_unsigned_float((16 << 6) | 0x20, 6), 3.0);
+        // Largest finite 11 bit value
+        assert_eq!(unpack_unsigned_float((30 << 6) | 0x3f, 6), 65024.0);
+        assert_eq!(unpack_unsigned_float(1, 5), (-19f32).exp2());
+        assert_eq!(unpack_unsigned_float(0, 6), 0.0);
+        assert_eq!(unpack_unsigned_float(31 << 5, 5), f32::INFINITY);
+        assert!(unpack_unsigned_float((31 << 6) | 1, 6).is_nan());
+    }
+
+    #[test]
+    fn test_float_formats() {
+        // R = 1023, G = 0, B = 512, A = 1
+        let value = 0x3ffu32 | (512 << 20) | (1 << 30);
+        let [r, g, b, a] = decode_float(DxgiFormat::R10G10B10A2_UNorm, &value.to_le_bytes());
+        assert_eq!([r, g, a], [1.0, 0.0, 1.0 / 3.0]);
+        assert!((b - 0.5).abs() < 0.001);
+        assert_eq!(
+            decode(DxgiFormat::R10G10B10A2_UNorm, &value.to_le_bytes()),
+            [255, 0, 128, 85]
+        );
+
+        // R = 1.0, G = 2.0, B = 0.5
+        let value = (15u32 << 6) | (16 << 17) | (14 << 27);
+        assert_eq!(
+            decode_float(DxgiFormat::R11G11B10_Float, &value.to_le_bytes()),
+            [1.0, 2.0, 0.5, 1.0]
+        );
+
+        // Exponent 16 scales the 9 bit mantissas by 2^-8
+        let value = 256u32 | (1 << 9) | (511 << 18) | (16 << 27);
+        assert_eq!(
+            decode_float(DxgiFormat::R9G9B9E5_SharedExp, &value.to_le_bytes()),
+            [1.0, 1.0 / 256.0, 511.0 / 256.0, 1.0]
+        );
+        assert_eq!(
+            decode_float(DxgiFormat::R9G9B9E5_SharedExp, &(31u32 << 27).to_le_bytes()),
+            [0.0, 0.0, 0.0, 1.0]
+        );
+
+        let halves = [1.0f32, -2.0, 0.5, 65504.0].map(|v| half::f16::from_f32(v).to_le_bytes());
+        assert_eq!(
+            decode_float(DxgiFormat::R16G16B16A16_Float, &halves.concat()),
+            [1.0, -2.0, 0.5, 65504.0]
+        );
+        assert_eq!(
+            decode_float(DxgiFormat::R16_Float, &halves[3]),
+            [65504.0, 65504.0, 65504.0, 1.0]
+        );
+
+        assert_eq!(
+            decode_float(DxgiFormat::R16G16_Float, &halves[..2].concat()),
+            [1.0, -2.0, 0.0, 1.0]
+        );
+
+        let floats = [0.25f32, 8.0, -1.0, 1.0].map(f32::to_le_bytes).concat();
+        assert_eq!(
+            decode_float(DxgiFormat::R32_Float, &floats[..4]),
+            [0.25, 0.25, 0.25, 1.0]
+        );
+        assert_eq!(
+            decode_float(DxgiFormat::R32G32_Float, &floats[..8]),
+            [0.25, 8.0, 0.0, 1.0]
+        );
+        assert_eq!(
+            decode_float(DxgiFormat::R32G32B32A32_Float, &floats),
+            [0.25, 8.0, -1.0, 1.0]
+        );
+        assert!(float_pixel_codec(DxgiFormat::R8G8B8A8_UNorm).is_none());
+    }
+
+    #[test]
+    fn test_channel_layouts() {
+        let decode = |format: DxgiFormat, bytes: &[u8]| {
+            let layout = channel_layout(format).unwrap();
+            assert_eq!(layout.pixel_size(), bytes.len());
+            layout.decode_pixel(bytes)
+        };
+
+        assert_eq!(
+            decode(DxgiFormat::R8G8_SNorm, &[127, 0x80]),
+            [1.0, -1.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            decode(DxgiFormat::R16G16_UNorm, &[0xff, 0xff, 0, 0]),
+            [1.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            decode(DxgiFormat::R16_UInt, &[0x34, 0x12]),
+            [4660.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(
+            decode(DxgiFormat::R32_SInt, &(-5i32).to_le_bytes()),
+            [-5.0, 0.0, 0.0, 0.0]
+        );
+        assert_eq!(decode(DxgiFormat::R8_SInt, &[0xff]), [-1.0, 0.0, 0.0, 0.0]);
+
+        let layout = channel_layout(DxgiFormat::R16_SInt).unwrap();
+        assert_eq!(layout.kind, ChannelKind::SInt);
+        assert_eq!(layout.range(), (-32768.0, 32767.0));
+        let layout = channel_layout(DxgiFormat::R32G32B32A32_UInt).unwrap();
+        assert_eq!(layout.range(), (0.0, u32::MAX as f64));
+        assert!(channel_layout(DxgiFormat::R16_Float).is_none());
+    }
+}
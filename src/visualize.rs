@@ -0,0 +1,131 @@
+use image::{Rgba, RgbaImage};
+
+use crate::uncompressed::ChannelLayout;
+
+/// How the raw values of UNORM, SNORM, UINT and SINT formats are turned into colours
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Visualization {
+    /// Map the whole range of the format onto [0, 255], e.g. -1.0 to 0 and 1.0 to 255 for SNORM
+    #[default]
+    Remap,
+    /// Stretch the lowest and highest value found in each colour channel onto [0, 255].
+    /// Alpha is remapped as usual, so a uniform alpha doesn't turn transparent
+    Normalize,
+    /// Give every distinct integer in the first channel its own colour, with 0 left black.
+    /// Meant for ID and index textures
+    FalseColour,
+}
+
+/// Turn row-major channel values, as decoded by [`ChannelLayout::decode_pixel`], into an image.
+/// Single channel formats come out grey and two channel formats as red and green, with alpha
+/// only taken from formats that have four channels
+pub fn visualize(
+    values: &[[f64; 4]],
+    width: u32,
+    height: u32,
+    layout: ChannelLayout,
+    mode: Visualization,
+) -> RgbaImage {
+    let ranges = match mode {
+        Visualization::Normalize => {
+            let mut ranges = [(f64::MAX, f64::MIN); 4];
+            values.iter().for_each(|v| {
+                ranges.iter_mut().zip(v).for_each(|((lo, hi), &v)| {
+                    *lo = lo.min(v);
+                    *hi = hi.max(v);
+                });
+            });
+            ranges[3] = layout.range();
+            ranges
+        }
+        _ => [layout.range(); 4],
+    };
+    // A channel holding a single value has nothing to stretch, so it comes out as 0
+    let to_byte = |v: f64, (lo, hi): (f64, f64)| {
+        if hi > lo {
+            ((v - lo) / (hi - lo) * 255.0).round().clamp(0.0, 255.0) as u8
+        } else {
+            0
+        }
+    };
+
+    RgbaImage::from_fn(width, height, |x, y| {
+        let v = values[(y * width + x) as usize];
+        if mode == Visualization::FalseColour {
+            return Rgba(false_colour(v[0] as i64));
+        }
+
+        let [r, g, b, a] = [0, 1, 2, 3].map(|i| to_byte(v[i], ranges[i]));
+        Rgba(match layout.channels {
+            1 => [r, r, r, 255],
+            2 => [r, g, 0, 255],
+            3 => [r, g, b, 255],
+            _ => [r, g, b, a],
+        })
+    })
+}
+
+/// Scatter IDs across the colour space with the SplitMix64 finaliser, so that neighbouring
+/// values get unrelated colours
+fn false_colour(id: i64) -> [u8; 4] {
+    if id == 0 {
+        return [0, 0, 0, 255];
+    }
+
+    let mut z = (id as u64).wrapping_add(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+
+    let [r, g, b, ..] = z.to_be_bytes();
+    [r, g, b, 255]
+}
+
+#[cfg(test)]
+mod tests {
+    use ddsfile::DxgiFormat;
+
+    use super::{visualize, Visualization};
+    use crate::uncompressed::channel_layout;
+
+    #[test]
+    fn test_visualize() {
+        let snorm = channel_layout(DxgiFormat::R8G8_SNorm).unwrap();
+        let values = [[-1.0, 1.0, 0.0, 0.0], [0.0, 0.5, 0.0, 0.0]];
+
+        let img = visualize(&values, 2, 1, snorm, Visualization::Remap);
+        assert_eq!(img.get_pixel(0, 0).0, [0, 255, 0, 255]);
+        assert_eq!(img.get_pixel(1, 0).0, [128, 191, 0, 255]);
+
+        let img = visualize(&values, 2, 1, snorm, Visualization::Normalize);
+        assert_eq!(img.get_pixel(0, 0).0, [0, 255, 0, 255]);
+        assert_eq!(img.get_pixel(1, 0).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_false_colour() {
+        let uint = channel_layout(DxgiFormat::R16_UInt).unwrap();
+        let values = [
+            [0.0; 4],
+            [1.0, 0.0, 0.0, 0.0],
+            [2.0, 0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+        ];
+        let img = visualize(&values, 4, 1, uint, Visualization::FalseColour);
+
+        assert_eq!(img.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_ne!(img.get_pixel(1, 0), img.get_pixel(2, 0));
+        assert_eq!(img.get_pixel(1, 0), img.get_pixel(3, 0));
+        assert_ne!(img.get_pixel(1, 0).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_normalize_constant_alpha() {
+        let snorm = channel_layout(DxgiFormat::R8G8B8A8_SNorm).unwrap();
+        let values = [[-0.5, 0.0, 0.0, 1.0], [0.5, 0.0, 0.0, 1.0]];
+
+        let img = visualize(&values, 2, 1, snorm, Visualization::Normalize);
+        assert_eq!(img.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(img.get_pixel(1, 0).0, [255, 0, 0, 255]);
+    }
+}